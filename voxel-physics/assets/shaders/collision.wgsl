@@ -0,0 +1,130 @@
+#import "shaders/world_data.wgsl"::{params, CellData, get_prev_cell};
+#import "shaders/constants.wgsl"::{PARTICLE_NOTHING, CELL_RADIUS, BOUNDARY_MODE, BOUNDARY_PERIODIC, COLLISION_MODE, COLLISION_BOUNCE};
+#import "shaders/utils.wgsl"::is_out_of_bounds;
+
+/// Collisions
+///
+/// Every particle moves to the cell pointed by its `relative_pos`, particles
+/// moving to the same cell, including the one staying in it, collide. With
+/// `COLLISION_MERGE` they always merge.
+///
+/// With `COLLISION_BOUNCE` every pair of the colliding particles approaching
+/// each other exchanges momentum along the line between their cells, with
+/// `params.restitution` of their approach speed left. The particles bounce
+/// off and stay in their cells, unless every pair approaches slower than
+/// `params.merge_speed`, then they merge as before.
+///
+/// A particle bounced back into its cell merges with the particles moving
+/// into the cell it was leaving.
+
+fn axis_to_dir(val: f32) -> i32 {
+    // TODO get rid of if
+    if val < -CELL_RADIUS {
+        return -1;
+    } else if val > CELL_RADIUS {
+        return 1;
+    }
+
+    return 0;
+}
+
+fn rel_pos_to_dir(rel_pos: vec2<f32>) -> vec2<i32> {
+    return vec2<i32>(axis_to_dir(rel_pos.x), axis_to_dir(rel_pos.y));
+}
+
+/// Whether the particle at `particle_pos` moves to the cell at `location`
+fn moves_to(particle: CellData, particle_pos: vec2<i32>, location: vec2<i32>) -> bool {
+    if particle.particle_type == PARTICLE_NOTHING {
+        return false;
+    }
+
+    let destination = particle_pos + rel_pos_to_dir(particle.relative_pos);
+    return destination.x == location.x && destination.y == location.y;
+}
+
+/// Particles moving to the same cell
+struct CollisionGroup {
+    count: u32,
+    positions: array<vec2<i32>, 9>,
+    particles: array<CellData, 9>,
+}
+
+/// Particles moving to `location`, empty without bouncing collisions or
+/// beyond the edges of a non-periodic world
+fn get_collision_group(location: vec2<i32>) -> CollisionGroup {
+    var group: CollisionGroup;
+    group.count = 0u;
+
+    if COLLISION_MODE != COLLISION_BOUNCE || (BOUNDARY_MODE != BOUNDARY_PERIODIC && is_out_of_bounds(location)) {
+        return group;
+    }
+
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let position = location + vec2<i32>(x, y);
+            let particle = get_prev_cell(position);
+            if moves_to(particle, position, location) {
+                group.positions[group.count] = position;
+                group.particles[group.count] = particle;
+                group.count += 1u;
+            }
+        }
+    }
+
+    return group;
+}
+
+/// Speed of the first particle towards the second one along the line between
+/// their cells, negative if they move apart
+fn approach_speed(first: CellData, first_pos: vec2<i32>, second: CellData, second_pos: vec2<i32>) -> f32 {
+    let normal = normalize(vec2<f32>(second_pos - first_pos));
+    return dot(first.impulse / first.mass - second.impulse / second.mass, normal);
+}
+
+/// Whether the particles of the group bounce off each other instead of
+/// merging, the same for every particle of the group
+fn bounces(collision_group: CollisionGroup) -> bool {
+    // arrays of values can't be indexed dynamically
+    var group = collision_group;
+    for (var i = 0u; i < group.count; i += 1u) {
+        for (var j = i + 1u; j < group.count; j += 1u) {
+            let speed = approach_speed(group.particles[i], group.positions[i], group.particles[j], group.positions[j]);
+            if speed >= params.merge_speed {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+/// Impulse the particle at `particle_pos` gets from the other particles of
+/// the group, impulses of every pair are opposite so momentum is conserved
+fn collision_impulse(collision_group: CollisionGroup, particle_pos: vec2<i32>) -> vec2<f32> {
+    var group = collision_group;
+    var impulse = vec2<f32>(0.0, 0.0);
+
+    var index = 0u;
+    for (var i = 0u; i < group.count; i += 1u) {
+        if all(group.positions[i] == particle_pos) {
+            index = i;
+        }
+    }
+    let particle = group.particles[index];
+
+    for (var i = 0u; i < group.count; i += 1u) {
+        if i == index {
+            continue;
+        }
+
+        let other = group.particles[i];
+        let speed = approach_speed(particle, particle_pos, other, group.positions[i]);
+        if speed > 0.0 {
+            let normal = normalize(vec2<f32>(group.positions[i] - particle_pos));
+            let reduced_mass = particle.mass * other.mass / (particle.mass + other.mass);
+            impulse -= normal * (1.0 + params.restitution) * reduced_mass * speed;
+        }
+    }
+
+    return impulse;
+}
@@ -4,18 +4,43 @@ const ERROR_COLOR = vec4<f32>(1.0, 1.0, 1.0, 1.0);
 const PARTICLE_NOTHING = 0u;
 /// Regular particle with mass and impulse
 const PARTICLE_REGULAR = 1u;
-const CHANCE_OF_PARTICLE = 0.001;
 
 const CELL_SIZE = 1.0;
 const CELL_RADIUS = 0.5;
 
 const PI = 3.14159265359;
 const EPSILON = 0.00001;
-const GRAVITY_CONSTANT = 1.0;
-const DEFAULT_MASS = 1.0;
 const CELL_CENTER = vec2<f32>(0.0, 0.0);
 
-const WORLD_WIDTH = 1024i;
-const WORLD_HEIGHT = 1024i;
-/// Default simulatuion step duration
-const DEFAULT_STEP_DURATION = 1.0f;
+/// Set from `GameWorldSize` via shader defs
+const WORLD_WIDTH = #{WORLD_WIDTH}i;
+const WORLD_HEIGHT = #{WORLD_HEIGHT}i;
+
+/// The world wraps around the edges
+const BOUNDARY_PERIODIC = 0u;
+/// Particles bounce off the edges
+const BOUNDARY_REFLECT = 1u;
+/// Particles crossing the edges are deleted, see `lost_mass`
+const BOUNDARY_ABSORB = 2u;
+
+/// Set from `BoundaryMode` via shader defs
+const BOUNDARY_MODE = #{BOUNDARY_MODE}u;
+
+/// Fields are relayed between neighbor cells, see `gravity_data.wgsl`
+const GRAVITY_RELAY = 0u;
+/// Field is evaluated from the mass pyramid, see `pyramid.wgsl`
+const GRAVITY_PYRAMID = 1u;
+/// Field is convolved from the mass density with FFT, see `fft.wgsl`
+const GRAVITY_FFT = 2u;
+
+/// Set from `GravityMode` via shader defs
+const GRAVITY_MODE = #{GRAVITY_MODE}u;
+
+/// Colliding particles always merge
+const COLLISION_MERGE = 0u;
+/// Colliding particles bounce off each other unless they are slow, see
+/// `collision.wgsl`
+const COLLISION_BOUNCE = 1u;
+
+/// Set from `CollisionMode` via shader defs
+const COLLISION_MODE = #{COLLISION_MODE}u;
@@ -0,0 +1,116 @@
+#import "shaders/world_data.wgsl"::{CellData, get_prev_cell, location_to_index};
+#import "shaders/constants.wgsl"::{WORLD_WIDTH, WORLD_HEIGHT, PI, PARTICLE_NOTHING};
+#import "shaders/gravity_data.wgsl"::{GravityData, gravity_data_from_field};
+
+/// FFT gravity
+///
+/// The potential of the periodic world solves the Poisson equation
+/// `∇²φ = 2πρ`, so the field of a mass is `mass / distance` like in a plane,
+/// and the field is its negative gradient. Both are products in the frequency
+/// domain: the spectrum of the density is divided by `-|k|²`, with the mean
+/// density dropped, and multiplied by the spectrum of the central difference.
+/// The field of a unit mass is zero at its own cell, so particles don't feel
+/// themselves.
+///
+/// The field is packed as a single complex number `x + iy`, and as the density
+/// is real, a single forward and inverse transform give both components. The
+/// spectrum of the field of a unit mass depends only on the world size and is
+/// computed once on the CPU, see `fft_kernel_spectrum` in `resources/fft.rs`.
+///
+/// Transforms are radix-2 Stockham passes along the rows and then along the
+/// columns, so both sizes of the world must be powers of two. Every pass reads
+/// one half of `fft` and writes the other one, see `fft_passes` in
+/// `resources/fft.rs`. Particles are taken at the centers of their cells.
+
+/// Two halves of `WORLD_WIDTH * WORLD_HEIGHT` complex values ping-ponged by
+/// the passes, the field is in the first half after the last pass
+@group(0) @binding(8) var<storage, read_write> fft: array<vec2<f32>>;
+/// Spectrum of the field of a unit mass
+@group(0) @binding(9) var<storage, read> fft_kernel: array<vec2<f32>>;
+/// Pass of the transform, bound separately for every pass
+@group(1) @binding(0) var<uniform> fft_params: FftParams;
+
+/// Keep in sync with `FftParams` in `resources/fft.rs`
+struct FftParams {
+    /// Half of `fft` read by the pass, the other half is written
+    source: u32,
+    /// Axis transformed by `fft_butterfly`, `0` for rows and `1` for columns
+    axis: u32,
+    /// Length of the transforms merged by `fft_butterfly`
+    span: u32,
+    /// Whether `fft_butterfly` runs the inverse transform
+    inverse: u32,
+}
+
+fn fft_index(half: u32, location: vec2<i32>) -> u32 {
+    return half * u32(WORLD_WIDTH * WORLD_HEIGHT) + u32(location.y * WORLD_WIDTH + location.x);
+}
+
+fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+/// Location of the element at `position` of the row or column `line`
+fn axis_location(axis: u32, position: i32, line: i32) -> vec2<i32> {
+    if axis == 0u {
+        return vec2<i32>(position, line);
+    }
+
+    return vec2<i32>(line, position);
+}
+
+fn axis_length(axis: u32) -> i32 {
+    if axis == 0u {
+        return WORLD_WIDTH;
+    }
+
+    return WORLD_HEIGHT;
+}
+
+/// Writes the mass of the cell as a complex number
+fn set_fft_density(params: FftParams, location: vec2<i32>) {
+    let cell = get_prev_cell(location);
+
+    var density = vec2<f32>(0.0, 0.0);
+    if cell.particle_type != PARTICLE_NOTHING {
+        density.x = cell.mass;
+    }
+
+    fft[fft_index(1u - params.source, location)] = density;
+}
+
+/// Radix-2 Stockham butterfly merging two transforms of length `span` of the
+/// row or column `line`, `pair` goes up to half of its length
+fn fft_butterfly_pair(params: FftParams, pair: i32, line: i32) {
+    let span = i32(params.span);
+    let half_length = axis_length(params.axis) / 2;
+    let k = pair % span;
+
+    var angle = -PI * f32(k) / f32(span);
+    if params.inverse != 0u {
+        angle = -angle;
+    }
+
+    let even = fft[fft_index(params.source, axis_location(params.axis, pair, line))];
+    let odd = fft[fft_index(params.source, axis_location(params.axis, pair + half_length, line))];
+    let twiddled = complex_mul(odd, vec2<f32>(cos(angle), sin(angle)));
+
+    let output = (pair / span) * span * 2 + k;
+    fft[fft_index(1u - params.source, axis_location(params.axis, output, line))] = even + twiddled;
+    fft[fft_index(1u - params.source, axis_location(params.axis, output + span, line))] = even - twiddled;
+}
+
+/// Multiplies the spectrum of the density by the spectrum of the field of a
+/// unit mass, which solves the Poisson equation for the field
+fn fft_convolve_cell(params: FftParams, location: vec2<i32>) {
+    let density = fft[fft_index(params.source, location)];
+    fft[fft_index(1u - params.source, location)] = complex_mul(density, fft_kernel[location_to_index(location)]);
+}
+
+/// Field of all particles except the one in the current cell, there is no
+/// single source so it is stored as a source at a unit distance
+fn get_fft_gravity_data(current_pos: vec2<i32>) -> GravityData {
+    // the inverse transform is not normalized
+    let field = fft[fft_index(0u, current_pos)] / f32(WORLD_WIDTH * WORLD_HEIGHT);
+    return gravity_data_from_field(field, 1.0);
+}
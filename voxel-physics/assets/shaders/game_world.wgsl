@@ -1,70 +1,152 @@
-#import "shaders/world_data.wgsl"::{texture, CellData, set_next_cell, get_prev_cell, new_empty_cell, new_particle_cell, cell_to_color};
+#import "shaders/world_data.wgsl"::{texture, params, lost_mass, CellData, set_next_cell, get_prev_cell, new_empty_cell, new_particle_cell, location_to_index};
 #import "shaders/constants.wgsl"::{
     WORLD_WIDTH,
     WORLD_HEIGHT,
-    DEFAULT_STEP_DURATION,
-    DEFAULT_MASS,
-    CHANCE_OF_PARTICLE,
     PARTICLE_NOTHING,
     PARTICLE_REGULAR,
-    PARTICLE_SOURCE,
-    CELL_RADIUS,
     CELL_CENTER,
-    EPSILON,
+    BOUNDARY_MODE,
+    BOUNDARY_REFLECT,
+    BOUNDARY_ABSORB,
+    GRAVITY_MODE,
+    GRAVITY_PYRAMID,
+    GRAVITY_FFT,
 };
 #import "shaders/gravity_data.wgsl"::{
+    GravityData,
+    field,
     get_cell_gravity_data,
 };
+#import "shaders/pyramid.wgsl"::{
+    pyramid_params,
+    level_layout,
+    set_node,
+    is_in_level,
+    build_pyramid_node,
+    get_pyramid_gravity_data,
+};
+#import "shaders/fft.wgsl"::{
+    fft_params,
+    axis_length,
+    set_fft_density,
+    fft_butterfly_pair,
+    fft_convolve_cell,
+    get_fft_gravity_data,
+};
+#import "shaders/collision.wgsl"::{
+    rel_pos_to_dir,
+    moves_to,
+    get_collision_group,
+    bounces,
+    collision_impulse,
+};
 #import "shaders/utils.wgsl"::{
     is_out_of_bounds,
 };
 #import "shaders/random.wgsl"::{
-    random_float,
+    random_float_seeded,
 };
+#import "shaders/visualization.wgsl"::{
+    cell_to_color,
+};
+#import "shaders/stats.wgsl"::{
+    stats,
+    StatsPartial,
+    no_stats,
+    cell_stats,
+    add_stats,
+};
+
+const WORKGROUP_CELLS = 64u;
+
+var<workgroup> workgroup_stats: array<StatsPartial, WORKGROUP_CELLS>;
 
 @compute @workgroup_size(8, 8, 1)
-fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
     if is_out_of_bounds(location) {
         return;
     }
 
-    if random_float(invocation_id.y * num_workgroups.x + invocation_id.x) < (1.0 - CHANCE_OF_PARTICLE) {
+    let index = location_to_index(location);
+    lost_mass[index] = 0.0;
+
+    if random_float_seeded(params.seed, index) < (1.0 - params.particle_chance) {
         set_next_cell(location, new_empty_cell());
     } else {
-        set_next_cell(location, new_particle_cell(DEFAULT_MASS, vec2<f32>(0.0, 0.0)));
+        set_next_cell(location, new_particle_cell(params.default_mass, vec2<f32>(0.0, 0.0)));
     }
+}
 
-    // let distance = 100;
+/// Called before every update step, copies the state as is
+@compute @workgroup_size(8, 8, 1)
+fn pre_update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
-    // let r = distance / 2;
-    // let center = vec2<i32>(WORLD_WIDTH / 2, WORLD_HEIGHT / 2);
-    // let left_cell_pos = center - vec2<i32>(r, 0);
-    // let right_cell_pos = center + vec2<i32>(r, 0);
+    if is_out_of_bounds(location) {
+        return;
+    }
 
-    
-    // if (location.x == left_cell_pos.x && location.y == left_cell_pos.y) {
-    //     let left_cell = new_particle_cell(DEFAULT_MASS, vec2<f32>(0.0, 0.0));
-    //     set_next_cell(location, left_cell);
-    // } else if (location.x == right_cell_pos.x && location.y == right_cell_pos.y) {
-    //     let right_cell = new_particle_cell(DEFAULT_MASS, vec2<f32>(0.0, 0.0));
-    //     set_next_cell(location, right_cell);
-    // } else {
-    //     set_next_cell(location, new_empty_cell());
-    // }
+    set_next_cell(location, get_prev_cell(location));
 }
 
-/// Called before every update step
+/// Builds `pyramid_params.level` of the mass pyramid, dispatched for every
+/// level from the base up right before `update_gravity` with pyramid gravity
 @compute @workgroup_size(8, 8, 1)
-fn pre_update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+fn build_pyramid(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let node = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+    let nodes = level_layout(pyramid_params.level);
+
+    if !is_in_level(node, nodes.size) {
+        return;
+    }
+
+    set_node(nodes, node, build_pyramid_node(pyramid_params.level, node));
+}
+
+/// Writes the mass density into `fft`, the first of the FFT gravity passes
+/// dispatched right before `update_gravity`
+@compute @workgroup_size(8, 8, 1)
+fn fft_density(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
     if is_out_of_bounds(location) {
         return;
     }
 
-    set_next_cell(location, get_prev_cell(location));
+    set_fft_density(fft_params, location);
+}
+
+/// A step of the transform along `fft_params.axis`, a butterfly per
+/// invocation
+@compute @workgroup_size(8, 8, 1)
+fn fft_butterfly(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    var pair = i32(invocation_id.x);
+    var line = i32(invocation_id.y);
+    if fft_params.axis != 0u {
+        pair = i32(invocation_id.y);
+        line = i32(invocation_id.x);
+    }
+
+    if pair >= axis_length(fft_params.axis) / 2 || line >= axis_length(1u - fft_params.axis) {
+        return;
+    }
+
+    fft_butterfly_pair(fft_params, pair, line);
+}
+
+/// Multiplies the spectrum of the density by the spectrum of the kernel,
+/// between the forward and inverse transforms
+@compute @workgroup_size(8, 8, 1)
+fn fft_convolve(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+
+    if is_out_of_bounds(location) {
+        return;
+    }
+
+    fft_convolve_cell(fft_params, location);
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -77,48 +159,22 @@ fn update_gravity(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     var current = get_prev_cell(location);
 
-    let gravity_00 = get_cell_gravity_data(current, location, location + vec2<i32>(-1, -1));
-    let gravity_10 = get_cell_gravity_data(current, location, location + vec2<i32>(0, -1));
-    let gravity_20 = get_cell_gravity_data(current, location, location + vec2<i32>(1, -1));
-    let gravity_01 = get_cell_gravity_data(current, location, location + vec2<i32>(-1, 0));
-    let gravity_21 = get_cell_gravity_data(current, location, location + vec2<i32>(1, 0));
-    let gravity_02 = get_cell_gravity_data(current, location, location + vec2<i32>(-1, 1));
-    let gravity_12 = get_cell_gravity_data(current, location, location + vec2<i32>(0, 1));
-    let gravity_22 = get_cell_gravity_data(current, location, location + vec2<i32>(1, 1));
-
-    let total_strength = (
-        gravity_00.strength
-        + gravity_10.strength
-        + gravity_20.strength
-        + gravity_01.strength
-        + gravity_21.strength
-        + gravity_02.strength
-        + gravity_12.strength
-        + gravity_22.strength
-    );
-
-    if total_strength < EPSILON {
-        current.to_gravity_source = vec2<f32>(0.0, 0.0);
-        current.gravity_strength = 0.0;
+    var gravity: GravityData;
+    if GRAVITY_MODE == GRAVITY_PYRAMID {
+        gravity = get_pyramid_gravity_data(current, location);
+    } else if GRAVITY_MODE == GRAVITY_FFT {
+        gravity = get_fft_gravity_data(location);
     } else {
-        current.to_gravity_source = (
-            gravity_00.to_source * gravity_00.strength
-            + gravity_10.to_source * gravity_10.strength
-            + gravity_20.to_source * gravity_20.strength
-            + gravity_01.to_source * gravity_01.strength
-            + gravity_21.to_source * gravity_21.strength
-            + gravity_02.to_source * gravity_02.strength
-            + gravity_12.to_source * gravity_12.strength
-            + gravity_22.to_source * gravity_22.strength
-        ) / total_strength;
-        current.gravity_strength = total_strength / 8.0;
+        gravity = get_cell_gravity_data(current, location);
     }
+    current.to_gravity_source = gravity.to_source;
+    current.gravity_strength = gravity.mass;
 
     set_next_cell(location, current);
 }
 
 fn delta_time() -> f32 {
-    return DEFAULT_STEP_DURATION;
+    return params.step_duration;
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -131,42 +187,42 @@ fn update_impulse(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     var current = get_prev_cell(location);
 
+    // every pass writes every cell, empty ones are kept as is
     if current.particle_type == PARTICLE_NOTHING {
+        set_next_cell(location, current);
         return;
     }
 
-    let dist_sq = current.to_gravity_source.x * current.to_gravity_source.x + current.to_gravity_source.y * current.to_gravity_source.y;
-
-    if dist_sq > EPSILON {
-        current.impulse += normalize(current.to_gravity_source) * current.mass * current.gravity_strength / dist_sq  * delta_time();
-    }
+    let gravity_field = field(GravityData(current.to_gravity_source, current.gravity_strength));
+    current.impulse += gravity_field * current.mass * params.gravity_constant * delta_time();
     
     current.relative_pos = current.impulse / current.mass * delta_time();
 
 
     // TODO cap max speed in a different way
-    if length(current.relative_pos - CELL_CENTER) > 1.0 {
-        current.relative_pos = CELL_CENTER + normalize(current.relative_pos - CELL_CENTER);
+    if length(current.relative_pos - CELL_CENTER) > params.max_speed {
+        current.relative_pos = CELL_CENTER + normalize(current.relative_pos - CELL_CENTER) * params.max_speed;
     }
 
-    set_next_cell(location, current);
-}
-
-fn axis_to_dir(val: f32) -> i32 {
-    // TODO get rid of if
-    if val < -CELL_RADIUS {
-        return -1;
-    } else if val > CELL_RADIUS {
-        return 1;
+    if BOUNDARY_MODE == BOUNDARY_REFLECT {
+        let destination = location + rel_pos_to_dir(current.relative_pos);
+        if destination.x < 0 || destination.x >= WORLD_WIDTH {
+            current.impulse.x = -current.impulse.x;
+            current.relative_pos.x = -current.relative_pos.x;
+        }
+        if destination.y < 0 || destination.y >= WORLD_HEIGHT {
+            current.impulse.y = -current.impulse.y;
+            current.relative_pos.y = -current.relative_pos.y;
+        }
     }
 
-    return 0;
-}
-
-fn rel_pos_to_dir(rel_pos: vec2<f32>) -> vec2<i32> {
-    return vec2<i32>(axis_to_dir(rel_pos.x), axis_to_dir(rel_pos.y));
+    set_next_cell(location, current);
 }
 
+/// Every particle moves to exactly one cell, the one pointed by its
+/// `relative_pos`. So every cell gathers all particles moving to it from
+/// `data_prev` and writes only itself, particles moving to the same cell
+/// collide, see `collision.wgsl`.
 @compute @workgroup_size(8, 8, 1)
 fn update_position(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
@@ -175,45 +231,113 @@ fn update_position(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
         return;
     }
 
-    var current = get_prev_cell(location);
-    var changed = false;
+    let current = get_prev_cell(location);
 
-    // go through all cells around and check if some cell is trying to move to this cell
-    for (var x = -1; x <= 1; x += 1) {
-        for (var y = -1; y <= 1; y += 1) {
-            if x == 0 && y == 0 {
-                continue;
+    var next = current;
+    if !moves_to(current, location, location) {
+        let destination = location + rel_pos_to_dir(current.relative_pos);
+
+        var bounced = false;
+        if current.particle_type != PARTICLE_NOTHING {
+            let destination_group = get_collision_group(destination);
+            if bounces(destination_group) {
+                // the particle bounces back and stays
+                next.impulse += collision_impulse(destination_group, location);
+                next.relative_pos = CELL_CENTER;
+                bounced = true;
             }
+        }
+
+        if !bounced {
+            if BOUNDARY_MODE == BOUNDARY_ABSORB && current.particle_type != PARTICLE_NOTHING && is_out_of_bounds(destination) {
+                lost_mass[location_to_index(location)] += current.mass;
+            }
+
+            // the particle leaves the cell, gravity data stays
+            next = new_empty_cell();
+            next.to_gravity_source = current.to_gravity_source;
+            next.gravity_strength = current.gravity_strength;
+        }
+    }
 
-            let pos = vec2<i32>(x, y);
-            let neightbor_pos = location + pos;
-            let neightbor = get_prev_cell(neightbor_pos);
+    let group = get_collision_group(location);
+    if bounces(group) {
+        // particles moving here bounce back and stay in their cells
+        if moves_to(current, location, location) {
+            next.impulse += collision_impulse(group, location);
+            next.relative_pos = CELL_CENTER;
+        }
+
+        set_next_cell(location, next);
+        return;
+    }
 
-            if neightbor.particle_type == PARTICLE_NOTHING {
+    // go through all cells around and check if some cell is moving to this cell
+    for (var x = -1; x <= 1; x += 1) {
+        for (var y = -1; y <= 1; y += 1) {
+            if x == 0 && y == 0 {
                 continue;
             }
 
-            let neightbor_relative_pos = rel_pos_to_dir(neightbor.relative_pos) + pos;            
+            let neighbor_pos = location + vec2<i32>(x, y);
+            let neighbor = get_prev_cell(neighbor_pos);
 
-            if neightbor_relative_pos.x != 0 || neightbor_relative_pos.y != 0 {
+            if !moves_to(neighbor, neighbor_pos, location) {
                 continue;
             }
 
             // merge cells
-            current.mass += neightbor.mass;
-            current.impulse += neightbor.impulse;
-            current.relative_pos = CELL_CENTER;
-            current.particle_type = PARTICLE_REGULAR;
-            changed = true;
-            set_next_cell(neightbor_pos, new_empty_cell());
+            next.mass += neighbor.mass;
+            next.impulse += neighbor.impulse;
+            next.relative_pos = CELL_CENTER;
+            next.particle_type = PARTICLE_REGULAR;
         }
     }
 
-    if changed {
-        set_next_cell(location, current);
+    set_next_cell(location, next);
+}
+
+/// Draws `data_prev` into the texture, dispatched once per frame after the
+/// simulation steps
+@compute @workgroup_size(8, 8, 1)
+fn draw(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+
+    if is_out_of_bounds(location) {
+        return;
     }
 
-    let color = cell_to_color(current);
+    textureStore(texture, location, cell_to_color(get_prev_cell(location)));
+}
+
+/// Sums up stats of `data_prev` per workgroup into `stats`, the partial sums
+/// are added up on the CPU.
+@compute @workgroup_size(8, 8, 1)
+fn reduce_stats(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+
+    // out of bounds invocations must still reach the barrier
+    if is_out_of_bounds(location) {
+        workgroup_stats[local_index] = no_stats();
+    } else {
+        workgroup_stats[local_index] = cell_stats(get_prev_cell(location), location, lost_mass[location_to_index(location)]);
+    }
+
+    workgroupBarrier();
+
+    if local_index != 0u {
+        return;
+    }
+
+    var total = no_stats();
+    for (var i = 0u; i < WORKGROUP_CELLS; i += 1u) {
+        total = add_stats(total, workgroup_stats[i]);
+    }
 
-    textureStore(texture, location, color);
+    stats[workgroup_id.y * num_workgroups.x + workgroup_id.x] = total;
 }
@@ -1,36 +1,146 @@
 #import "shaders/world_data.wgsl"::{CellData, get_prev_cell};
 #import "shaders/constants.wgsl"::{EPSILON, PARTICLE_NOTHING};
 
+/// Gravity model
+///
+/// Every cell stores the field of all particles except the one in the cell
+/// itself as an equivalent point source: `to_gravity_source` is the vector
+/// from the cell to the source and `gravity_strength` is its mass, so the
+/// field is `mass * to_source / |to_source|^3` (without the gravity constant).
+///
+/// `update_gravity` averages the estimates of the field made from the 8
+/// neighbors:
+///
+/// - an empty neighbor's source is re-expressed relative to the current cell,
+///   so it keeps its mass and falls off with the actual distance to it.
+/// - a particle neighbor adds its own field, its stored field is used as is.
+/// - a particle in the current cell subtracts its own field from what the
+///   neighbor feels.
+///
+/// The averaged field is stored back as a point source at the strength
+/// weighted distance of the estimates. Sources propagate one cell per step.
+/// The field of a single body is exact and radially symmetric, several bodies
+/// are approximated.
 struct GravityData {
     /// position of gravity source relative to current cell
     to_source: vec2<f32>,
-    strength: f32,
+    /// mass of gravity source
+    mass: f32,
 }
 
-// TODO add mass to calculations
-fn get_cell_gravity_data(current_cell: CellData, current_pos: vec2<i32>, neighbor_pos: vec2<i32>) -> GravityData {
-    let neighbor_cell = get_prev_cell(neighbor_pos);
+fn no_gravity_data() -> GravityData {
+    return GravityData(vec2<f32>(0.0, 0.0), 0.0);
+}
+
+/// Point source producing the given field at the given distance
+fn gravity_data_from_field(field: vec2<f32>, distance: f32) -> GravityData {
+    let strength = length(field);
+    if strength < EPSILON || distance < EPSILON {
+        return no_gravity_data();
+    }
+
+    return GravityData(field / strength * distance, strength * distance * distance);
+}
+
+/// Strength of the field produced by the source, without the gravity constant
+fn field_strength(gravity: GravityData) -> f32 {
+    let dist_sq = dot(gravity.to_source, gravity.to_source);
+    if gravity.mass < EPSILON || dist_sq < EPSILON {
+        return 0.0;
+    }
 
-    let vec_to_cell = vec2<f32>(neighbor_pos - current_pos) + neighbor_cell.relative_pos - current_cell.relative_pos;
-    let distance = length(vec_to_cell);
-    
-    // TODO fix this
-    if distance == 0.0 {
-        return GravityData (vec2<f32>(0.0, 0.0), 0.0);
+    return gravity.mass / dist_sq;
+}
+
+/// Field produced by the source, without the gravity constant
+fn field(gravity: GravityData) -> vec2<f32> {
+    let strength = field_strength(gravity);
+    if strength == 0.0 {
+        return vec2<f32>(0.0, 0.0);
     }
 
-    if neighbor_cell.particle_type == PARTICLE_NOTHING {
-        if neighbor_cell.gravity_strength < EPSILON {
-            return GravityData (vec2<f32>(0.0, 0.0), 0.0);
+    return normalize(gravity.to_source) * strength;
+}
+
+/// Sum of fields with the distances to their sources weighted by strength
+struct FieldEstimate {
+    field: vec2<f32>,
+    weight: f32,
+    weighted_distance: f32,
+}
+
+fn no_field_estimate() -> FieldEstimate {
+    return FieldEstimate(vec2<f32>(0.0, 0.0), 0.0, 0.0);
+}
+
+fn add_field(estimate: FieldEstimate, field: vec2<f32>, distance: f32) -> FieldEstimate {
+    let strength = length(field);
+    return FieldEstimate(
+        estimate.field + field,
+        estimate.weight + strength,
+        estimate.weighted_distance + strength * distance,
+    );
+}
+
+/// Vector from the current cell to the neighbor cell
+fn vec_to_neighbor(current_cell: CellData, current_pos: vec2<i32>, neighbor_pos: vec2<i32>, neighbor_cell: CellData) -> vec2<f32> {
+    return vec2<f32>(neighbor_pos - current_pos) + neighbor_cell.relative_pos - current_cell.relative_pos;
+}
+
+/// Field of the current cell estimated from the neighbor, see the gravity
+/// model above
+fn get_neighbor_field(current_cell: CellData, current_pos: vec2<i32>, neighbor_pos: vec2<i32>) -> FieldEstimate {
+    let neighbor_cell = get_prev_cell(neighbor_pos);
+    let to_neighbor = vec_to_neighbor(current_cell, current_pos, neighbor_pos, neighbor_cell);
+    let stored = GravityData(neighbor_cell.to_gravity_source, neighbor_cell.gravity_strength);
+
+    var estimate = no_field_estimate();
+
+    if field_strength(stored) > 0.0 {
+        if current_cell.particle_type != PARTICLE_NOTHING {
+            // the neighbor feels the current particle too
+            let own = GravityData(-to_neighbor, current_cell.mass);
+            estimate = add_field(estimate, field(stored) - field(own), length(stored.to_source));
+        } else if neighbor_cell.particle_type != PARTICLE_NOTHING {
+            estimate = add_field(estimate, field(stored), length(stored.to_source));
+        } else {
+            let shifted = GravityData(to_neighbor + stored.to_source, stored.mass);
+            estimate = add_field(estimate, field(shifted), length(shifted.to_source));
         }
+    }
 
-        let gravity_source_rel_pos = vec_to_cell + neighbor_cell.to_gravity_source;
+    if neighbor_cell.particle_type != PARTICLE_NOTHING {
+        let particle = GravityData(to_neighbor, neighbor_cell.mass);
+        estimate = add_field(estimate, field(particle), length(to_neighbor));
+    }
+
+    return estimate;
+}
+
+/// Field of all particles except the one in the current cell as a point source
+fn get_cell_gravity_data(current_cell: CellData, current_pos: vec2<i32>) -> GravityData {
+    var total = no_field_estimate();
+    var informed = 0;
+
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            if x == 0 && y == 0 {
+                continue;
+            }
+
+            let estimate = get_neighbor_field(current_cell, current_pos, current_pos + vec2<i32>(x, y));
+            if estimate.weight > 0.0 {
+                total.field += estimate.field;
+                total.weight += estimate.weight;
+                total.weighted_distance += estimate.weighted_distance;
+                informed += 1;
+            }
+        }
+    }
 
-        return GravityData(
-            gravity_source_rel_pos,
-            neighbor_cell.gravity_strength,
-        );
+    if informed == 0 {
+        return no_gravity_data();
     }
 
-    return GravityData (vec_to_cell, neighbor_cell.mass);
+    return gravity_data_from_field(total.field / f32(informed), total.weighted_distance / total.weight);
 }
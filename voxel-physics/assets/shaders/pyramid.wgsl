@@ -0,0 +1,242 @@
+#import "shaders/world_data.wgsl"::{CellData, get_prev_cell};
+#import "shaders/constants.wgsl"::{WORLD_WIDTH, WORLD_HEIGHT, EPSILON, PARTICLE_NOTHING, BOUNDARY_MODE, BOUNDARY_PERIODIC};
+#import "shaders/gravity_data.wgsl"::{GravityData, no_gravity_data, gravity_data_from_field, field, FieldEstimate, no_field_estimate, add_field};
+
+/// Mass pyramid
+///
+/// The base level has a node per cell and every next level has a node per 2x2
+/// nodes of the previous one, up to the top level which is no larger than
+/// `PYRAMID_TOP_SIZE` nodes in both directions. A node stores the total mass
+/// and mass moment of the cells it covers, so it acts as a point mass at their
+/// center of mass.
+///
+/// The field of a cell sums up every other particle exactly once:
+///
+/// - particles in the 8 neighbor cells, from the base level.
+/// - at every level below the top, the children of the parent's neighbors
+///   which are not neighbors of the cell's own node. They are at least a node
+///   away, so the further the particles, the coarser the nodes.
+/// - at the top level, all nodes which are not neighbors of the cell's node.
+///
+/// Neighbors wrap around the edges of periodic worlds and nodes are taken at
+/// their nearest image.
+
+/// All levels one after another, starting from the base
+@group(0) @binding(7) var<storage, read_write> pyramid: array<PyramidNode>;
+/// Level written by `build_pyramid`, bound separately for every level
+@group(1) @binding(0) var<uniform> pyramid_params: PyramidParams;
+
+/// Keep in sync with `PyramidNode` in `resources/pyramid.rs`
+struct PyramidNode {
+    /// Sum of particle positions weighted by mass
+    mass_moment: vec2<f32>,
+    mass: f32,
+}
+
+/// Keep in sync with `PyramidParams` in `resources/pyramid.rs`
+struct PyramidParams {
+    level: u32,
+}
+
+/// Keep in sync with `PYRAMID_TOP_SIZE` in `constants.rs`
+const PYRAMID_TOP_SIZE = 4;
+
+struct LevelLayout {
+    /// Index of the first node of the level in `pyramid`
+    offset: u32,
+    /// Size of the level in nodes
+    size: vec2<i32>,
+}
+
+fn level_size(level: u32) -> vec2<i32> {
+    let scale = 1 << level;
+    return (vec2<i32>(WORLD_WIDTH, WORLD_HEIGHT) + scale - 1) / scale;
+}
+
+fn level_layout(level: u32) -> LevelLayout {
+    var offset = 0u;
+    for (var lower = 0u; lower < level; lower += 1u) {
+        let size = level_size(lower);
+        offset += u32(size.x * size.y);
+    }
+
+    return LevelLayout(offset, level_size(level));
+}
+
+fn top_level() -> u32 {
+    var level = 0u;
+    var size = level_size(level);
+    while size.x > PYRAMID_TOP_SIZE || size.y > PYRAMID_TOP_SIZE {
+        level += 1u;
+        size = level_size(level);
+    }
+
+    return level;
+}
+
+fn no_node() -> PyramidNode {
+    return PyramidNode(vec2<f32>(0.0, 0.0), 0.0);
+}
+
+fn get_node(nodes: LevelLayout, node: vec2<i32>) -> PyramidNode {
+    return pyramid[nodes.offset + u32(node.y * nodes.size.x + node.x)];
+}
+
+fn set_node(nodes: LevelLayout, node: vec2<i32>, value: PyramidNode) {
+    pyramid[nodes.offset + u32(node.y * nodes.size.x + node.x)] = value;
+}
+
+fn cell_node(cell: CellData, location: vec2<i32>) -> PyramidNode {
+    if cell.particle_type == PARTICLE_NOTHING {
+        return no_node();
+    }
+
+    return PyramidNode((vec2<f32>(location) + cell.relative_pos) * cell.mass, cell.mass);
+}
+
+/// Node of the level summing up its children, base nodes are built from the
+/// cells in `data_prev`
+fn build_pyramid_node(level: u32, node: vec2<i32>) -> PyramidNode {
+    if level == 0u {
+        return cell_node(get_prev_cell(node), node);
+    }
+
+    let children = level_layout(level - 1u);
+    var total = no_node();
+    for (var y = 0; y < 2; y += 1) {
+        for (var x = 0; x < 2; x += 1) {
+            let child = node * 2 + vec2<i32>(x, y);
+            if is_in_level(child, children.size) {
+                let value = get_node(children, child);
+                total.mass_moment += value.mass_moment;
+                total.mass += value.mass;
+            }
+        }
+    }
+
+    return total;
+}
+
+fn is_in_level(node: vec2<i32>, size: vec2<i32>) -> bool {
+    return node.x >= 0 && node.x < size.x && node.y >= 0 && node.y < size.y;
+}
+
+/// Node at `offset` from `node`, wrapped around the edges of periodic worlds
+fn offset_node(node: vec2<i32>, offset: vec2<i32>, size: vec2<i32>) -> vec2<i32> {
+    let moved = node + offset;
+    if BOUNDARY_MODE == BOUNDARY_PERIODIC {
+        return (moved % size + size) % size;
+    }
+
+    return moved;
+}
+
+fn is_repeated_axis_offset(offset: i32, size: i32) -> bool {
+    return (size == 1 && offset != 0) || (size == 2 && offset == 1);
+}
+
+/// Whether an earlier offset in `-1..=1` reaches the same node, happens when a
+/// periodic level is less than 3 nodes wide
+fn is_repeated_offset(offset: vec2<i32>, size: vec2<i32>) -> bool {
+    if BOUNDARY_MODE != BOUNDARY_PERIODIC {
+        return false;
+    }
+
+    return is_repeated_axis_offset(offset.x, size.x) || is_repeated_axis_offset(offset.y, size.y);
+}
+
+/// Whether the nodes are neighbors or the same node
+fn are_near(a: vec2<i32>, b: vec2<i32>, size: vec2<i32>) -> bool {
+    var distance = abs(a - b);
+    if BOUNDARY_MODE == BOUNDARY_PERIODIC {
+        distance = min(distance, size - distance);
+    }
+
+    return distance.x <= 1 && distance.y <= 1;
+}
+
+/// Shortest vector between the points, across the edges of periodic worlds
+fn nearest_image(offset: vec2<f32>) -> vec2<f32> {
+    if BOUNDARY_MODE != BOUNDARY_PERIODIC {
+        return offset;
+    }
+
+    let world = vec2<f32>(f32(WORLD_WIDTH), f32(WORLD_HEIGHT));
+    return offset - world * floor(offset / world + 0.5);
+}
+
+/// Adds the field of the node as a point mass at its center of mass
+fn add_node_field(estimate: FieldEstimate, node: PyramidNode, position: vec2<f32>) -> FieldEstimate {
+    if node.mass < EPSILON {
+        return estimate;
+    }
+
+    let to_source = nearest_image(node.mass_moment / node.mass - position);
+    return add_field(estimate, field(GravityData(to_source, node.mass)), length(to_source));
+}
+
+/// Field of all particles except the one in the current cell as a point
+/// source, see the mass pyramid above
+fn get_pyramid_gravity_data(current_cell: CellData, current_pos: vec2<i32>) -> GravityData {
+    let position = vec2<f32>(current_pos) + current_cell.relative_pos;
+    let top = top_level();
+    var estimate = no_field_estimate();
+
+    let base = level_layout(0u);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let offset = vec2<i32>(x, y);
+            if (x == 0 && y == 0) || is_repeated_offset(offset, base.size) {
+                continue;
+            }
+
+            let neighbor = offset_node(current_pos, offset, base.size);
+            if is_in_level(neighbor, base.size) {
+                estimate = add_node_field(estimate, get_node(base, neighbor), position);
+            }
+        }
+    }
+
+    for (var level = 0u; level < top; level += 1u) {
+        let nodes = level_layout(level);
+        let parents = level_layout(level + 1u);
+        let node = current_pos >> vec2<u32>(level);
+        let parent = node >> vec2<u32>(1u);
+
+        for (var y = -1; y <= 1; y += 1) {
+            for (var x = -1; x <= 1; x += 1) {
+                let offset = vec2<i32>(x, y);
+                if is_repeated_offset(offset, parents.size) {
+                    continue;
+                }
+
+                let neighbor = offset_node(parent, offset, parents.size);
+                for (var child_y = 0; child_y < 2; child_y += 1) {
+                    for (var child_x = 0; child_x < 2; child_x += 1) {
+                        let child = neighbor * 2 + vec2<i32>(child_x, child_y);
+                        if is_in_level(child, nodes.size) && !are_near(child, node, nodes.size) {
+                            estimate = add_node_field(estimate, get_node(nodes, child), position);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let nodes = level_layout(top);
+    let node = current_pos >> vec2<u32>(top);
+    for (var y = 0; y < nodes.size.y; y += 1) {
+        for (var x = 0; x < nodes.size.x; x += 1) {
+            let other = vec2<i32>(x, y);
+            if !are_near(other, node, nodes.size) {
+                estimate = add_node_field(estimate, get_node(nodes, other), position);
+            }
+        }
+    }
+
+    if estimate.weight <= 0.0 {
+        return no_gravity_data();
+    }
+
+    return gravity_data_from_field(estimate.field, estimate.weighted_distance / estimate.weight);
+}
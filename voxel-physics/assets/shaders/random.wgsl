@@ -13,3 +13,8 @@ fn hash(value: u32) -> u32 {
 fn random_float(value: u32) -> f32 {
     return f32(hash(value)) / 4294967295.0;
 }
+
+/// Random float in `[0, 1]` for `value`, different for every `seed`
+fn random_float_seeded(seed: u32, value: u32) -> f32 {
+    return random_float(seed ^ hash(value));
+}
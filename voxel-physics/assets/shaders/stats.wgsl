@@ -0,0 +1,46 @@
+#import "shaders/world_data.wgsl"::CellData;
+#import "shaders/constants.wgsl"::PARTICLE_NOTHING;
+
+/// Bound next to the world data, one element per workgroup of `reduce_stats`
+@group(0) @binding(4) var<storage, read_write> stats: array<StatsPartial>;
+
+/// Keep in sync with `StatsPartial` in `resources/stats.rs`
+struct StatsPartial {
+    mass: f32,
+    particle_count: u32,
+    momentum: vec2<f32>,
+    /// Sum of particle positions weighted by mass
+    mass_moment: vec2<f32>,
+    /// Mass absorbed by the edges
+    lost_mass: f32,
+}
+
+fn no_stats() -> StatsPartial {
+    return StatsPartial(0.0, 0u, vec2<f32>(0.0, 0.0), vec2<f32>(0.0, 0.0), 0.0);
+}
+
+fn cell_stats(cell: CellData, location: vec2<i32>, cell_lost_mass: f32) -> StatsPartial {
+    var stats = no_stats();
+    stats.lost_mass = cell_lost_mass;
+
+    if cell.particle_type == PARTICLE_NOTHING {
+        return stats;
+    }
+
+    let position = vec2<f32>(location) + cell.relative_pos;
+    stats.mass = cell.mass;
+    stats.particle_count = 1u;
+    stats.momentum = cell.impulse;
+    stats.mass_moment = position * cell.mass;
+    return stats;
+}
+
+fn add_stats(a: StatsPartial, b: StatsPartial) -> StatsPartial {
+    return StatsPartial(
+        a.mass + b.mass,
+        a.particle_count + b.particle_count,
+        a.momentum + b.momentum,
+        a.mass_moment + b.mass_moment,
+        a.lost_mass + b.lost_mass,
+    );
+}
@@ -0,0 +1,142 @@
+#import "shaders/world_data.wgsl"::CellData;
+#import "shaders/gravity_data.wgsl"::{GravityData, field_strength};
+#import "shaders/constants.wgsl"::{PARTICLE_NOTHING, PARTICLE_REGULAR, ERROR_COLOR, PI, EPSILON};
+
+@group(0) @binding(6) var<uniform> visualization: VisualizationParams;
+
+/// Keep in sync with `VisualizationParams` in `resources/uniforms.rs`
+struct VisualizationParams {
+    /// One of `VISUALIZATION_*`
+    mode: u32,
+    /// Mass at the top of the mass density colormap
+    max_mass: f32,
+    /// Field strength at the top of the gravity colormaps
+    max_field: f32,
+    /// Speed at the top of the velocity colormap
+    max_speed: f32,
+}
+
+// Keep in sync with `VisualizationMode` in `resources/visualization.rs`
+
+/// Gravity direction for empty cells and impulse for particles
+const VISUALIZATION_COMBINED = 0u;
+/// Particle mass on a log scale, viridis
+const VISUALIZATION_MASS_DENSITY = 1u;
+/// Field strength on a log scale, magma
+const VISUALIZATION_GRAVITY_STRENGTH = 2u;
+/// Field direction as hue and strength as brightness
+const VISUALIZATION_GRAVITY_DIRECTION = 3u;
+/// Particle speed, magma
+const VISUALIZATION_VELOCITY = 4u;
+/// Flat color per particle type
+const VISUALIZATION_PARTICLE_TYPE = 5u;
+
+/// Number of decades of field strength below `max_field` the gravity
+/// colormaps cover
+const FIELD_DECADES = 4.0;
+
+/// Polynomial fit of matplotlib's viridis, `t` in `[0, 1]`
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+
+    let x = saturate(t);
+    return saturate(c0 + x * (c1 + x * (c2 + x * (c3 + x * (c4 + x * (c5 + x * c6))))));
+}
+
+/// Polynomial fit of matplotlib's magma, `t` in `[0, 1]`
+fn magma(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(-0.002136485053939582, -0.000749655052795221, -0.005386127855323933);
+    let c1 = vec3<f32>(0.2516605407371642, 0.6775232436837668, 2.494026599312351);
+    let c2 = vec3<f32>(8.353717279216625, -3.577719514958484, 0.3144679030132573);
+    let c3 = vec3<f32>(-27.66873308576866, 14.26473078096533, -13.64921318813922);
+    let c4 = vec3<f32>(52.17613981234068, -27.94360607168351, 12.94416944238394);
+    let c5 = vec3<f32>(-50.76852536473588, 29.04658282127291, 4.23415299384598);
+    let c6 = vec3<f32>(18.65570506591883, -11.48977351997711, -5.601961508734096);
+
+    let x = saturate(t);
+    return saturate(c0 + x * (c1 + x * (c2 + x * (c3 + x * (c4 + x * (c5 + x * c6))))));
+}
+
+/// Fully saturated color of the hue, `hue` in `[0, 1]`
+fn hue_to_rgb(hue: f32) -> vec3<f32> {
+    let k = (vec3<f32>(0.0, 2.0, 1.0) / 3.0 + hue) * 6.0;
+    return saturate(abs(fract(k / 6.0) * 6.0 - 3.0) - 1.0);
+}
+
+/// Position of the field strength of the cell on the gravity colormaps
+fn field_strength_t(cell: CellData) -> f32 {
+    let strength = field_strength(GravityData(cell.to_gravity_source, cell.gravity_strength));
+    if strength < EPSILON * EPSILON {
+        return 0.0;
+    }
+
+    return 1.0 + log(strength / visualization.max_field) / log(10.0) / FIELD_DECADES;
+}
+
+fn combined_color(cell: CellData) -> vec3<f32> {
+    if cell.particle_type == PARTICLE_NOTHING {
+        let gravity_color = cell.to_gravity_source * 0.5 + 0.5;
+        return vec3<f32>(gravity_color.x, 0.0, gravity_color.y);
+    }
+
+    let impulse_color = cell.impulse * 0.5 + 0.5;
+    return vec3<f32>(impulse_color.x, 1.0, impulse_color.y);
+}
+
+fn mass_density_color(cell: CellData) -> vec3<f32> {
+    return viridis(log(1.0 + cell.mass) / log(1.0 + visualization.max_mass));
+}
+
+fn gravity_direction_color(cell: CellData) -> vec3<f32> {
+    let angle = atan2(cell.to_gravity_source.y, cell.to_gravity_source.x);
+    return hue_to_rgb(angle / (2.0 * PI) + 0.5) * saturate(field_strength_t(cell));
+}
+
+fn velocity_color(cell: CellData) -> vec3<f32> {
+    if cell.particle_type == PARTICLE_NOTHING {
+        return magma(0.0);
+    }
+
+    return magma(length(cell.impulse / cell.mass) / visualization.max_speed);
+}
+
+fn particle_type_color(cell: CellData) -> vec3<f32> {
+    if cell.particle_type == PARTICLE_NOTHING {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    } else if cell.particle_type == PARTICLE_REGULAR {
+        return viridis(0.5);
+    }
+
+    return ERROR_COLOR.rgb;
+}
+
+fn cell_to_color(cell: CellData) -> vec4<f32> {
+    if cell.particle_type != PARTICLE_NOTHING && cell.particle_type != PARTICLE_REGULAR {
+        return ERROR_COLOR;
+    }
+
+    var color: vec3<f32>;
+    if visualization.mode == VISUALIZATION_COMBINED {
+        color = combined_color(cell);
+    } else if visualization.mode == VISUALIZATION_MASS_DENSITY {
+        color = mass_density_color(cell);
+    } else if visualization.mode == VISUALIZATION_GRAVITY_STRENGTH {
+        color = magma(field_strength_t(cell));
+    } else if visualization.mode == VISUALIZATION_GRAVITY_DIRECTION {
+        color = gravity_direction_color(cell);
+    } else if visualization.mode == VISUALIZATION_VELOCITY {
+        color = velocity_color(cell);
+    } else if visualization.mode == VISUALIZATION_PARTICLE_TYPE {
+        color = particle_type_color(cell);
+    } else {
+        return ERROR_COLOR;
+    }
+
+    return vec4<f32>(color, 1.0);
+}
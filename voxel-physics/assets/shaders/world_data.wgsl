@@ -1,13 +1,39 @@
-#import "shaders/constants.wgsl"::{WORLD_WIDTH, WORLD_HEIGHT, PARTICLE_NOTHING, PARTICLE_REGULAR, CELL_CENTER, ERROR_COLOR};
+#import "shaders/constants.wgsl"::{WORLD_WIDTH, WORLD_HEIGHT, PARTICLE_NOTHING, PARTICLE_REGULAR, CELL_CENTER, BOUNDARY_MODE, BOUNDARY_PERIODIC};
+#import "shaders/utils.wgsl"::is_out_of_bounds;
 
 @group(0) @binding(0) var texture: texture_storage_2d<rgba8unorm, read_write>;
 @group(0) @binding(1) var<storage, read_write> data_prev: array<CellData>;
 @group(0) @binding(2) var<storage, read_write> data_next: array<CellData>;
+@group(0) @binding(3) var<uniform> params: SimulationParams;
+/// Mass of particles absorbed by the edges per cell they left the world from,
+/// accumulated since the world was initialized
+@group(0) @binding(5) var<storage, read_write> lost_mass: array<f32>;
+
+/// Keep in sync with `SimulationParams` in `resources/uniforms.rs`
+struct SimulationParams {
+    /// Duration of a single simulation step
+    step_duration: f32,
+    gravity_constant: f32,
+    /// Chance of a cell to be filled with a particle by `init`
+    particle_chance: f32,
+    /// Mass of particles spawned by `init`
+    default_mass: f32,
+    /// Max distance in cells particle can travel during a single step
+    max_speed: f32,
+    /// Seed of the random distribution generated by `init`
+    seed: u32,
+    /// Part of the approach speed left after a bounce, see `collision.wgsl`
+    restitution: f32,
+    /// Colliding particles slower than this merge instead of bouncing
+    merge_speed: f32,
+}
 
-
+/// Keep in sync with `CellData` in `resources/data.rs`
 struct CellData {
-    /// Position of gravity source relative to current cell
+    /// Position of gravity source relative to current cell, see
+    /// `gravity_data.wgsl`
     to_gravity_source: vec2<f32>,
+    /// Mass of gravity source
     gravity_strength: f32,
     particle_type: u32,
     mass: f32,
@@ -21,30 +47,9 @@ fn new_empty_cell() -> CellData {
 
 fn new_particle_cell(mass: f32, particle_vel: vec2<f32>) -> CellData {
     let impulse: vec2<f32> = particle_vel * mass;
-    return CellData(vec2<f32>(0.0, 0.0), mass, PARTICLE_REGULAR, mass, impulse, CELL_CENTER);
-}
-
-fn empty_cell_color(cell: CellData) -> vec4<f32> {
-    let gravity_color = cell.to_gravity_source * 0.5 + 0.5;
-    return vec4<f32>(gravity_color.x, 0.0, gravity_color.y, 1.0);
+    return CellData(vec2<f32>(0.0, 0.0), 0.0, PARTICLE_REGULAR, mass, impulse, CELL_CENTER);
 }
 
-fn particle_cell_color(cell: CellData) -> vec4<f32> {
-    let impulse_color = cell.impulse * 0.5 + 0.5;
-    return vec4<f32>(impulse_color.x, 1.0, impulse_color.y, 1.0);
-}
-
-fn cell_to_color(cell: CellData) -> vec4<f32> {
-    if cell.particle_type == PARTICLE_NOTHING {
-        return empty_cell_color(cell);
-    } else if cell.particle_type == PARTICLE_REGULAR {
-        return particle_cell_color(cell);
-    }
-
-    return ERROR_COLOR;
-}
-
-
 fn true_mod(a: i32, b: i32) -> i32 {
     return (a % b + b) % b;
 }
@@ -70,7 +75,12 @@ fn set_next_cell(location: vec2<i32>, value: CellData) {
     data_next[location_to_index(location)] = value;
 }
 
-/// Get cell data from previous frame
+/// Get cell data from previous frame, cells beyond the edges are empty unless
+/// the world is periodic
 fn get_prev_cell(location: vec2<i32>) -> CellData {
+    if BOUNDARY_MODE != BOUNDARY_PERIODIC && is_out_of_bounds(location) {
+        return new_empty_cell();
+    }
+
     return data_prev[location_to_index(location)];
 }
@@ -0,0 +1,228 @@
+use std::{
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    app::{AppExit, ScheduleRunnerPlugin},
+    prelude::*,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use clap::Parser;
+use voxel_physics::game_world::{
+    BoundaryMode, CollisionMode, FrameRecording, GameWorldPlugin, GameWorldSize, GravityMode,
+    PipelineCompilationFailed, SaveWorldFrame, SaveWorldSnapshot, SimulationControl,
+    SimulationProgress, SimulationStatsUpdated, WorldFileFailed, WorldFrameSaved,
+    WorldSnapshotSaved, DEFAULT_RECORDING_INTERVAL, DEFAULT_SEED, DEFAULT_WORLD_SIZE,
+};
+
+/// Runs the simulation without a window.
+#[derive(Clone, Debug, Parser, Resource)]
+#[command(version, about)]
+struct Args {
+    /// World width in cells
+    #[arg(long, default_value_t = DEFAULT_WORLD_SIZE.0)]
+    width: u32,
+    /// World height in cells
+    #[arg(long, default_value_t = DEFAULT_WORLD_SIZE.1)]
+    height: u32,
+    /// Seed of the initial random distribution
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    seed: u32,
+    /// Scenario file to start from instead of the random distribution
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+    /// What happens at the edges of the world: periodic, reflect or absorb
+    #[arg(long, default_value_t = BoundaryMode::Periodic)]
+    boundary: BoundaryMode,
+    /// How the field is computed: relay, pyramid or fft
+    #[arg(long, default_value_t = GravityMode::Relay)]
+    gravity: GravityMode,
+    /// What happens to particles moving to the same cell: merge or bounce
+    #[arg(long, default_value_t = CollisionMode::Merge)]
+    collisions: CollisionMode,
+    /// Number of full simulation steps to run
+    #[arg(long)]
+    steps: u64,
+    /// Path to save the world snapshot to after the last step
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Path to save the world texture to as a PNG image after the last step
+    #[arg(long)]
+    output_image: Option<PathBuf>,
+    /// Directory to record the world texture into as a numbered PNG sequence
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Steps between recorded frames
+    #[arg(long, default_value_t = DEFAULT_RECORDING_INTERVAL)]
+    record_interval: u64,
+    /// Log stats every N steps, 0 disables logging
+    #[arg(long, default_value_t = 100)]
+    stats_interval: u64,
+    /// Max number of full simulation steps dispatched every frame
+    #[arg(long, default_value_t = 16)]
+    steps_per_frame: u32,
+}
+
+/// Set when the run fails, `App::run` doesn't return the world.
+#[derive(Clone, Debug, Default, Resource)]
+struct Failed(Arc<AtomicBool>);
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let world_size = GameWorldSize((args.width, args.height));
+    if let Err(err) = args.gravity.check(world_size, args.boundary) {
+        eprintln!("error: {err}");
+        return ExitCode::FAILURE;
+    }
+    let failed = Failed::default();
+
+    App::new()
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+        .add_plugins(GameWorldPlugin {
+            world_size: (args.width, args.height),
+            seed: args.seed,
+            scenario: args.scenario.clone(),
+            boundary: args.boundary,
+            gravity: args.gravity,
+            collisions: args.collisions,
+        })
+        .insert_resource(SimulationControl {
+            // as fast as possible
+            tick_rate: None,
+            steps_per_frame: args.steps_per_frame,
+            stop_at_step: Some(args.steps),
+            ..default()
+        })
+        .insert_resource(FrameRecording {
+            directory: args.record.clone(),
+            interval: args.record_interval,
+        })
+        .insert_resource(args)
+        .insert_resource(failed.clone())
+        .add_systems(Update, (log_stats_sys, finish_sys, exit_on_error_sys))
+        .run();
+
+    if failed.0.load(Ordering::Relaxed) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn log_stats_sys(
+    args: Res<Args>,
+    mut stats_events: EventReader<SimulationStatsUpdated>,
+    time: Res<Time>,
+    mut last_logged: Local<Option<(u64, f32)>>,
+) {
+    if args.stats_interval == 0 {
+        return;
+    }
+
+    for SimulationStatsUpdated(stats) in stats_events.read() {
+        let (last_step, last_time) =
+            last_logged.get_or_insert((stats.step, time.elapsed_seconds()));
+        if stats.step < *last_step + args.stats_interval {
+            continue;
+        }
+
+        let elapsed = time.elapsed_seconds() - *last_time;
+        let steps_per_second = (stats.step - *last_step) as f32 / elapsed.max(f32::EPSILON);
+        info!(
+            "step {}/{} ({steps_per_second:.1} steps/s): {} particles, mass {:.4}, momentum {:.4}, lost mass {:.4}",
+            stats.step,
+            args.steps,
+            stats.particle_count,
+            stats.total_mass,
+            stats.total_momentum,
+            stats.lost_mass
+        );
+
+        *last_logged = Some((stats.step, time.elapsed_seconds()));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn finish_sys(
+    args: Res<Args>,
+    progress: Res<SimulationProgress>,
+    mut save_snapshot_events: EventWriter<SaveWorldSnapshot>,
+    mut save_frame_events: EventWriter<SaveWorldFrame>,
+    mut snapshot_saved_events: EventReader<WorldSnapshotSaved>,
+    mut frame_saved_events: EventReader<WorldFrameSaved>,
+    mut exit_events: EventWriter<AppExit>,
+    mut pending: Local<Option<Vec<PathBuf>>>,
+) {
+    if !progress.initialized || progress.step < args.steps {
+        return;
+    }
+
+    let pending = pending.get_or_insert_with(|| {
+        if let Some(output) = &args.output {
+            save_snapshot_events.send(SaveWorldSnapshot(output.clone()));
+        }
+        if let Some(output_image) = &args.output_image {
+            save_frame_events.send(SaveWorldFrame(output_image.clone()));
+        }
+
+        // frames are written in the order they are recorded, the last one is
+        // recorded at the last multiple of the interval
+        let last_frame = args.record.as_ref().map(|directory| {
+            directory.join(format!(
+                "{:06}.png",
+                args.steps / args.record_interval.max(1)
+            ))
+        });
+
+        args.output
+            .iter()
+            .chain(&args.output_image)
+            .cloned()
+            .chain(last_frame)
+            .collect()
+    });
+
+    let saved: Vec<_> = snapshot_saved_events
+        .read()
+        .map(|saved| &saved.path)
+        .chain(frame_saved_events.read().map(|saved| &saved.path))
+        .collect();
+    pending.retain(|path| !saved.contains(&path));
+
+    if pending.is_empty() {
+        exit_events.send(AppExit);
+    }
+}
+
+/// The simulation never starts if the shaders don't compile, the run never
+/// finishes if an output can't be written, and it runs the wrong experiment if
+/// the scenario can't be loaded.
+fn exit_on_error_sys(
+    mut pipeline_failed_events: EventReader<PipelineCompilationFailed>,
+    mut file_failed_events: EventReader<WorldFileFailed>,
+    mut exit_events: EventWriter<AppExit>,
+    failed: Res<Failed>,
+) {
+    let pipeline_failed = pipeline_failed_events.read().next().is_some();
+    let file_failed = file_failed_events.read().next().is_some();
+    if pipeline_failed || file_failed {
+        failed.0.store(true, Ordering::Relaxed);
+        exit_events.send(AppExit);
+    }
+}
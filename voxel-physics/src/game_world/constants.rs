@@ -1,7 +1,63 @@
-pub const WORLD_SIZE: (u32, u32) = (1024, 1024);
+use bevy::prelude::*;
+
+pub const DEFAULT_WORLD_SIZE: (u32, u32) = (1024, 1024);
 pub const WORKGROUP_SIZE: u32 = 8;
 
 pub const DEFAULT_SENSITIVITY: f32 = 100.0;
 pub const MIN_SCALE: f32 = 0.025;
 pub const MAX_SCALE: f32 = 1.0;
 pub const DEFAULT_SCALE: f32 = 0.5;
+
+pub const DEFAULT_STEPS_PER_FRAME: u32 = 1;
+pub const MAX_STEPS_PER_FRAME: u32 = 64;
+/// Default full simulation steps per second
+pub const DEFAULT_TICK_RATE: f32 = 240.0;
+pub const MIN_TICK_RATE: f32 = 1.0;
+pub const MAX_TICK_RATE: f32 = 15360.0;
+
+pub const DEFAULT_BRUSH_RADIUS: f32 = 4.0;
+pub const DEFAULT_BRUSH_MASS: f32 = 1.0;
+
+pub const DEFAULT_COLOR_MAX_MASS: f32 = 100.0;
+pub const DEFAULT_COLOR_MAX_FIELD: f32 = 1.0;
+pub const DEFAULT_COLOR_MAX_SPEED: f32 = 1.0;
+
+/// Frames whose stats can be read back at the same time
+pub const STATS_READBACKS: usize = 4;
+
+/// Cells read back around the inspected cell in each direction
+pub const INSPECTOR_RADIUS: i32 = 2;
+
+/// Max size of the top level of the mass pyramid in both directions, keep in
+/// sync with `pyramid.wgsl`
+pub const PYRAMID_TOP_SIZE: u32 = 4;
+
+/// Snapshot file used by quick save and quick load keys
+pub const QUICK_SNAPSHOT_PATH: &str = "quicksave.snapshot";
+/// Mass of a white pixel of a mass map loaded without a scenario
+pub const DEFAULT_MASS_MAP_MASS: f32 = 1.0;
+/// Steps between frames written while recording
+pub const DEFAULT_RECORDING_INTERVAL: u64 = 10;
+/// Frames of the world texture which can be read back at the same time
+pub const FRAME_READBACKS: usize = 4;
+
+/// Default simulation step duration
+pub const DEFAULT_STEP_DURATION: f32 = 1.0;
+pub const DEFAULT_GRAVITY_CONSTANT: f32 = 1.0;
+pub const DEFAULT_PARTICLE_CHANCE: f32 = 0.001;
+pub const DEFAULT_MASS: f32 = 1.0;
+pub const DEFAULT_MAX_SPEED: f32 = 1.0;
+pub const DEFAULT_SEED: u32 = 0;
+pub const DEFAULT_RESTITUTION: f32 = 0.5;
+pub const DEFAULT_MERGE_SPEED: f32 = 0.5;
+
+// Keep in sync with `assets/shaders/constants.wgsl`
+
+/// Just an empty void
+pub const PARTICLE_NOTHING: u32 = 0;
+/// Regular particle with mass and impulse
+pub const PARTICLE_REGULAR: u32 = 1;
+
+pub const CELL_RADIUS: f32 = 0.5;
+pub const EPSILON: f32 = 0.00001;
+pub const CELL_CENTER: Vec2 = Vec2::ZERO;
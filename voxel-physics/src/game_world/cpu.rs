@@ -0,0 +1,1618 @@
+//! CPU reference implementation of the compute passes from
+//! `assets/shaders/game_world.wgsl`.
+//!
+//! Every pass reads from the previous state and writes to the next state the
+//! same way the shader does with `data_prev`/`data_next`, so the resulting
+//! [`CellData`] can be compared with the storage buffers for parity.
+
+use bevy::prelude::*;
+
+use crate::game_world::{
+    complex_mul, fft_butterfly, fft_index, fft_kernel_spectrum, fft_passes, pyramid_level_sizes,
+    BoundaryMode, CellData, CollisionMode, FftParams, FftStep, GameWorldSize, GravityMode,
+    PyramidNode, SimulationParams, CELL_CENTER, CELL_RADIUS, EPSILON, PARTICLE_NOTHING,
+    PARTICLE_REGULAR,
+};
+
+/// Same as `hash` in `random.wgsl`
+pub fn hash(value: u32) -> u32 {
+    let mut state = value;
+    state ^= 2747636419;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state
+}
+
+/// Same as `random_float` in `random.wgsl`
+pub fn random_float(value: u32) -> f32 {
+    hash(value) as f32 / 4294967295.0
+}
+
+/// Same as `random_float_seeded` in `random.wgsl`
+pub fn random_float_seeded(seed: u32, value: u32) -> f32 {
+    random_float(seed ^ hash(value))
+}
+
+/// Same as `GravityData` in `gravity_data.wgsl`, see it for the gravity model
+#[derive(Clone, Copy, Debug)]
+struct GravityData {
+    /// position of gravity source relative to current cell
+    to_source: Vec2,
+    /// mass of gravity source
+    mass: f32,
+}
+
+impl GravityData {
+    /// Same as `no_gravity_data` in `gravity_data.wgsl`
+    const NONE: Self = Self {
+        to_source: Vec2::ZERO,
+        mass: 0.0,
+    };
+
+    /// Gravity source stored in the cell
+    fn of_cell(cell: &CellData) -> Self {
+        Self {
+            to_source: cell.to_gravity_source,
+            mass: cell.gravity_strength,
+        }
+    }
+
+    /// Same as `gravity_data_from_field` in `gravity_data.wgsl`
+    fn from_field(field: Vec2, distance: f32) -> Self {
+        let strength = field.length();
+        if strength < EPSILON || distance < EPSILON {
+            return Self::NONE;
+        }
+
+        Self {
+            to_source: field / strength * distance,
+            mass: strength * distance * distance,
+        }
+    }
+
+    /// Same as `field_strength` in `gravity_data.wgsl`
+    fn field_strength(&self) -> f32 {
+        let dist_sq = self.to_source.length_squared();
+        if self.mass < EPSILON || dist_sq < EPSILON {
+            return 0.0;
+        }
+
+        self.mass / dist_sq
+    }
+
+    /// Same as `field` in `gravity_data.wgsl`
+    fn field(&self) -> Vec2 {
+        let strength = self.field_strength();
+        if strength == 0.0 {
+            return Vec2::ZERO;
+        }
+
+        self.to_source.normalize() * strength
+    }
+}
+
+/// Same as `FieldEstimate` in `gravity_data.wgsl`
+#[derive(Clone, Copy, Debug, Default)]
+struct FieldEstimate {
+    field: Vec2,
+    weight: f32,
+    weighted_distance: f32,
+}
+
+impl FieldEstimate {
+    /// Same as `add_field` in `gravity_data.wgsl`
+    fn add_field(&mut self, field: Vec2, distance: f32) {
+        let strength = field.length();
+        self.field += field;
+        self.weight += strength;
+        self.weighted_distance += strength * distance;
+    }
+}
+
+/// Same as `LevelLayout` in `pyramid.wgsl`
+#[derive(Clone, Copy, Debug)]
+struct LevelLayout {
+    /// Index of the first node of the level in `pyramid`
+    offset: usize,
+    /// Size of the level in nodes
+    size: IVec2,
+}
+
+/// Same as `level_layout` in `pyramid.wgsl` for every level
+fn level_layouts(size: (u32, u32)) -> Vec<LevelLayout> {
+    let mut offset = 0;
+    pyramid_level_sizes(GameWorldSize(size))
+        .into_iter()
+        .map(|(width, height)| {
+            let nodes = LevelLayout {
+                offset,
+                size: IVec2::new(width as i32, height as i32),
+            };
+            offset += (width * height) as usize;
+            nodes
+        })
+        .collect()
+}
+
+/// World state simulated on the CPU.
+#[derive(Clone, Debug)]
+pub struct CpuWorld {
+    /// Same as `params` uniform
+    pub params: SimulationParams,
+    /// Same as `BOUNDARY_MODE` shader def
+    pub boundary: BoundaryMode,
+    /// Same as `GRAVITY_MODE` shader def
+    pub gravity: GravityMode,
+    /// Same as `COLLISION_MODE` shader def
+    pub collisions: CollisionMode,
+    size: (u32, u32),
+    /// The previous state of the world, same as `data_prev`
+    data_prev: Vec<CellData>,
+    /// The next state of the world, same as `data_next`
+    data_next: Vec<CellData>,
+    /// Same as `lost_mass`
+    lost_mass: Vec<f32>,
+    pyramid_levels: Vec<LevelLayout>,
+    /// Same as `pyramid`
+    pyramid: Vec<PyramidNode>,
+    /// Same as `fft`
+    fft: Vec<Vec2>,
+    /// Same as `fft_kernel`, computed once the FFT passes run
+    fft_kernel: Vec<Vec2>,
+}
+
+impl CpuWorld {
+    /// Creates world filled with zeroed cells, same as freshly created
+    /// storage buffers.
+    pub fn new(size: (u32, u32)) -> Self {
+        let data = vec![CellData::default(); (size.0 * size.1) as usize];
+        Self::from_cells(size, data)
+    }
+
+    /// Creates world from the existing state, both buffers are filled with
+    /// `cells`.
+    pub fn from_cells(size: (u32, u32), cells: Vec<CellData>) -> Self {
+        assert_eq!(
+            cells.len(),
+            (size.0 * size.1) as usize,
+            "cells count does not match world size"
+        );
+
+        let pyramid_levels = level_layouts(size);
+        let top = pyramid_levels.last().unwrap();
+        let pyramid_nodes = top.offset + (top.size.x * top.size.y) as usize;
+
+        Self {
+            params: SimulationParams::default(),
+            boundary: BoundaryMode::default(),
+            gravity: GravityMode::default(),
+            collisions: CollisionMode::default(),
+            size,
+            data_prev: cells.clone(),
+            data_next: cells,
+            lost_mass: vec![0.0; (size.0 * size.1) as usize],
+            pyramid_levels,
+            pyramid: vec![PyramidNode::default(); pyramid_nodes],
+            fft: vec![Vec2::ZERO; 2 * (size.0 * size.1) as usize],
+            fft_kernel: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// The previous state of the world. (Array of [`CellData`])
+    pub fn data_prev(&self) -> &[CellData] {
+        &self.data_prev
+    }
+
+    /// The next state of the world. (Array of [`CellData`])
+    pub fn data_next(&self) -> &[CellData] {
+        &self.data_next
+    }
+
+    /// Mass absorbed by the edges per cell. (Same as `lost_mass`)
+    pub fn lost_mass(&self) -> &[f32] {
+        &self.lost_mass
+    }
+
+    /// Swaps the previous and next state of the world.
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.data_prev, &mut self.data_next);
+    }
+
+    /// Runs `init` pass followed by a swap, so the result is available in
+    /// [`CpuWorld::data_prev`].
+    pub fn run_init(&mut self) {
+        self.init();
+        self.swap();
+    }
+
+    /// Runs one full simulation step (pre-update, gravity, impulse and
+    /// position passes), every pass is followed by a swap the same way
+    /// `GameWorldNode` dispatches them.
+    pub fn step(&mut self) {
+        self.pre_update();
+        self.swap();
+
+        match self.gravity {
+            GravityMode::Relay => {}
+            GravityMode::Pyramid => self.build_pyramid_levels(),
+            GravityMode::Fft => self.run_fft_passes(),
+        }
+        self.update_gravity();
+        self.swap();
+
+        self.update_impulse();
+        self.swap();
+
+        self.update_position();
+        self.swap();
+    }
+
+    /// Same as `init` entry point
+    pub fn init(&mut self) {
+        for location in self.locations() {
+            let index = self.location_to_index(location);
+            self.lost_mass[index] = 0.0;
+
+            if random_float_seeded(self.params.seed, index as u32)
+                < (1.0 - self.params.particle_chance)
+            {
+                self.set_next_cell(location, CellData::new_empty());
+            } else {
+                self.set_next_cell(
+                    location,
+                    CellData::new_particle(self.params.default_mass, Vec2::ZERO),
+                );
+            }
+        }
+    }
+
+    /// Same as `pre_update` entry point
+    pub fn pre_update(&mut self) {
+        self.data_next.copy_from_slice(&self.data_prev);
+    }
+
+    /// Same as `build_pyramid` entry point
+    pub fn build_pyramid(&mut self, level: u32) {
+        let nodes = self.pyramid_levels[level as usize];
+        for y in 0..nodes.size.y {
+            for x in 0..nodes.size.x {
+                let node = IVec2::new(x, y);
+                let value = self.build_pyramid_node(level, node);
+                self.set_node(nodes, node, value);
+            }
+        }
+    }
+
+    /// Runs `build_pyramid` for every level from the base up, the same way
+    /// `GameWorldNode` dispatches it before `update_gravity`.
+    pub fn build_pyramid_levels(&mut self) {
+        for level in 0..self.pyramid_levels.len() {
+            self.build_pyramid(level as u32);
+        }
+    }
+
+    /// Same as `fft_density` entry point
+    pub fn fft_density(&mut self, params: &FftParams) {
+        for location in self.locations() {
+            let cell = self.get_prev_cell(location);
+
+            let mut density = Vec2::ZERO;
+            if cell.particle_type != PARTICLE_NOTHING {
+                density.x = cell.mass;
+            }
+
+            self.fft[fft_index(self.size, 1 - params.source, location)] = density;
+        }
+    }
+
+    /// Same as `fft_convolve` entry point
+    pub fn fft_convolve(&mut self, params: &FftParams) {
+        for location in self.locations() {
+            let density = self.fft[fft_index(self.size, params.source, location)];
+            let kernel = self.fft_kernel[self.location_to_index(location)];
+            self.fft[fft_index(self.size, 1 - params.source, location)] =
+                complex_mul(density, kernel);
+        }
+    }
+
+    /// Runs every pass of [`fft_passes`] the same way `GameWorldNode`
+    /// dispatches them before `update_gravity`, the kernel spectrum is
+    /// computed the first time.
+    pub fn run_fft_passes(&mut self) {
+        if self.fft_kernel.is_empty() {
+            self.fft_kernel = fft_kernel_spectrum(GameWorldSize(self.size));
+        }
+
+        for (step, params) in fft_passes(GameWorldSize(self.size)) {
+            match step {
+                FftStep::Density => self.fft_density(&params),
+                FftStep::Butterfly => fft_butterfly(&mut self.fft, self.size, &params),
+                FftStep::Convolve => self.fft_convolve(&params),
+            }
+        }
+    }
+
+    /// Same as `update_gravity` entry point
+    pub fn update_gravity(&mut self) {
+        for location in self.locations() {
+            let mut current = self.get_prev_cell(location);
+
+            let gravity = match self.gravity {
+                GravityMode::Relay => self.get_cell_gravity_data(&current, location),
+                GravityMode::Pyramid => self.get_pyramid_gravity_data(&current, location),
+                GravityMode::Fft => self.get_fft_gravity_data(location),
+            };
+            current.to_gravity_source = gravity.to_source;
+            current.gravity_strength = gravity.mass;
+
+            self.set_next_cell(location, current);
+        }
+    }
+
+    /// Same as `update_impulse` entry point
+    pub fn update_impulse(&mut self) {
+        let delta_time = self.params.step_duration;
+
+        for location in self.locations() {
+            let mut current = self.get_prev_cell(location);
+
+            if current.particle_type == PARTICLE_NOTHING {
+                self.set_next_cell(location, current);
+                continue;
+            }
+
+            let gravity_field = GravityData::of_cell(&current).field();
+            current.impulse +=
+                gravity_field * current.mass * self.params.gravity_constant * delta_time;
+
+            current.relative_pos = current.impulse / current.mass * delta_time;
+
+            if (current.relative_pos - CELL_CENTER).length() > self.params.max_speed {
+                current.relative_pos = CELL_CENTER
+                    + (current.relative_pos - CELL_CENTER).normalize() * self.params.max_speed;
+            }
+
+            if self.boundary == BoundaryMode::Reflect {
+                let destination = location + rel_pos_to_dir(current.relative_pos);
+                if destination.x < 0 || destination.x >= self.size.0 as i32 {
+                    current.impulse.x = -current.impulse.x;
+                    current.relative_pos.x = -current.relative_pos.x;
+                }
+                if destination.y < 0 || destination.y >= self.size.1 as i32 {
+                    current.impulse.y = -current.impulse.y;
+                    current.relative_pos.y = -current.relative_pos.y;
+                }
+            }
+
+            self.set_next_cell(location, current);
+        }
+    }
+
+    /// Same as `update_position` entry point
+    pub fn update_position(&mut self) {
+        for location in self.locations() {
+            let current = self.get_prev_cell(location);
+
+            let mut next = current;
+            if !moves_to(&current, location, location) {
+                let destination = location + rel_pos_to_dir(current.relative_pos);
+
+                let mut bounced = false;
+                if current.particle_type != PARTICLE_NOTHING {
+                    let destination_group = self.get_collision_group(destination);
+                    if self.bounces(&destination_group) {
+                        // the particle bounces back and stays
+                        next.impulse += self.collision_impulse(&destination_group, location);
+                        next.relative_pos = CELL_CENTER;
+                        bounced = true;
+                    }
+                }
+
+                if !bounced {
+                    if self.boundary == BoundaryMode::Absorb
+                        && current.particle_type != PARTICLE_NOTHING
+                        && self.is_out_of_bounds(destination)
+                    {
+                        let index = self.location_to_index(location);
+                        self.lost_mass[index] += current.mass;
+                    }
+
+                    // the particle leaves the cell, gravity data stays
+                    next = CellData {
+                        to_gravity_source: current.to_gravity_source,
+                        gravity_strength: current.gravity_strength,
+                        ..CellData::new_empty()
+                    };
+                }
+            }
+
+            let group = self.get_collision_group(location);
+            if self.bounces(&group) {
+                // particles moving here bounce back and stay in their cells
+                if moves_to(&current, location, location) {
+                    next.impulse += self.collision_impulse(&group, location);
+                    next.relative_pos = CELL_CENTER;
+                }
+
+                self.set_next_cell(location, next);
+                continue;
+            }
+
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    if x == 0 && y == 0 {
+                        continue;
+                    }
+
+                    let neighbor_pos = location + IVec2::new(x, y);
+                    let neighbor = self.get_prev_cell(neighbor_pos);
+
+                    if !moves_to(&neighbor, neighbor_pos, location) {
+                        continue;
+                    }
+
+                    // merge cells
+                    next.mass += neighbor.mass;
+                    next.impulse += neighbor.impulse;
+                    next.relative_pos = CELL_CENTER;
+                    next.particle_type = PARTICLE_REGULAR;
+                }
+            }
+
+            self.set_next_cell(location, next);
+        }
+    }
+
+    /// Same as `get_collision_group` in `collision.wgsl`, positions and
+    /// particles of the group
+    fn get_collision_group(&self, location: IVec2) -> Vec<(IVec2, CellData)> {
+        if self.collisions != CollisionMode::Bounce
+            || (self.boundary != BoundaryMode::Periodic && self.is_out_of_bounds(location))
+        {
+            return Vec::new();
+        }
+
+        let mut group = Vec::new();
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let position = location + IVec2::new(x, y);
+                let particle = self.get_prev_cell(position);
+                if moves_to(&particle, position, location) {
+                    group.push((position, particle));
+                }
+            }
+        }
+
+        group
+    }
+
+    /// Same as `bounces` in `collision.wgsl`
+    fn bounces(&self, group: &[(IVec2, CellData)]) -> bool {
+        for (i, (first_pos, first)) in group.iter().enumerate() {
+            for (second_pos, second) in &group[i + 1..] {
+                let speed = approach_speed(first, *first_pos, second, *second_pos);
+                if speed >= self.params.merge_speed {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Same as `collision_impulse` in `collision.wgsl`
+    fn collision_impulse(&self, group: &[(IVec2, CellData)], particle_pos: IVec2) -> Vec2 {
+        let mut impulse = Vec2::ZERO;
+
+        let Some((_, particle)) = group.iter().find(|(position, _)| *position == particle_pos)
+        else {
+            return impulse;
+        };
+
+        for (other_pos, other) in group {
+            if *other_pos == particle_pos {
+                continue;
+            }
+
+            let speed = approach_speed(particle, particle_pos, other, *other_pos);
+            if speed > 0.0 {
+                let normal = (*other_pos - particle_pos).as_vec2().normalize();
+                let reduced_mass = particle.mass * other.mass / (particle.mass + other.mass);
+                impulse -= normal * (1.0 + self.params.restitution) * reduced_mass * speed;
+            }
+        }
+
+        impulse
+    }
+
+    /// Same as `get_neighbor_field` in `gravity_data.wgsl`
+    fn get_neighbor_field(
+        &self,
+        current_cell: &CellData,
+        current_pos: IVec2,
+        neighbor_pos: IVec2,
+    ) -> FieldEstimate {
+        let neighbor_cell = self.get_prev_cell(neighbor_pos);
+        let to_neighbor = vec_to_neighbor(current_cell, current_pos, neighbor_pos, &neighbor_cell);
+        let stored = GravityData::of_cell(&neighbor_cell);
+
+        let mut estimate = FieldEstimate::default();
+
+        if stored.field_strength() > 0.0 {
+            if current_cell.particle_type != PARTICLE_NOTHING {
+                // the neighbor feels the current particle too
+                let own = GravityData {
+                    to_source: -to_neighbor,
+                    mass: current_cell.mass,
+                };
+                estimate.add_field(stored.field() - own.field(), stored.to_source.length());
+            } else if neighbor_cell.particle_type != PARTICLE_NOTHING {
+                estimate.add_field(stored.field(), stored.to_source.length());
+            } else {
+                let shifted = GravityData {
+                    to_source: to_neighbor + stored.to_source,
+                    mass: stored.mass,
+                };
+                estimate.add_field(shifted.field(), shifted.to_source.length());
+            }
+        }
+
+        if neighbor_cell.particle_type != PARTICLE_NOTHING {
+            let particle = GravityData {
+                to_source: to_neighbor,
+                mass: neighbor_cell.mass,
+            };
+            estimate.add_field(particle.field(), to_neighbor.length());
+        }
+
+        estimate
+    }
+
+    /// Same as `get_cell_gravity_data` in `gravity_data.wgsl`
+    fn get_cell_gravity_data(&self, current_cell: &CellData, current_pos: IVec2) -> GravityData {
+        let mut total = FieldEstimate::default();
+        let mut informed = 0;
+
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+
+                let estimate = self.get_neighbor_field(
+                    current_cell,
+                    current_pos,
+                    current_pos + IVec2::new(x, y),
+                );
+                if estimate.weight > 0.0 {
+                    total.field += estimate.field;
+                    total.weight += estimate.weight;
+                    total.weighted_distance += estimate.weighted_distance;
+                    informed += 1;
+                }
+            }
+        }
+
+        if informed == 0 {
+            return GravityData::NONE;
+        }
+
+        GravityData::from_field(
+            total.field / informed as f32,
+            total.weighted_distance / total.weight,
+        )
+    }
+
+    fn get_node(&self, nodes: LevelLayout, node: IVec2) -> PyramidNode {
+        self.pyramid[nodes.offset + (node.y * nodes.size.x + node.x) as usize]
+    }
+
+    fn set_node(&mut self, nodes: LevelLayout, node: IVec2, value: PyramidNode) {
+        self.pyramid[nodes.offset + (node.y * nodes.size.x + node.x) as usize] = value;
+    }
+
+    /// Same as `build_pyramid_node` in `pyramid.wgsl`
+    fn build_pyramid_node(&self, level: u32, node: IVec2) -> PyramidNode {
+        if level == 0 {
+            return cell_node(&self.get_prev_cell(node), node);
+        }
+
+        let children = self.pyramid_levels[level as usize - 1];
+        let mut total = PyramidNode::default();
+        for y in 0..2 {
+            for x in 0..2 {
+                let child = node * 2 + IVec2::new(x, y);
+                if is_in_level(child, children.size) {
+                    let value = self.get_node(children, child);
+                    total.mass_moment += value.mass_moment;
+                    total.mass += value.mass;
+                }
+            }
+        }
+
+        total
+    }
+
+    /// Same as `offset_node` in `pyramid.wgsl`
+    fn offset_node(&self, node: IVec2, offset: IVec2, size: IVec2) -> IVec2 {
+        let moved = node + offset;
+        if self.boundary == BoundaryMode::Periodic {
+            return (moved % size + size) % size;
+        }
+
+        moved
+    }
+
+    /// Same as `is_repeated_offset` in `pyramid.wgsl`
+    fn is_repeated_offset(&self, offset: IVec2, size: IVec2) -> bool {
+        if self.boundary != BoundaryMode::Periodic {
+            return false;
+        }
+
+        is_repeated_axis_offset(offset.x, size.x) || is_repeated_axis_offset(offset.y, size.y)
+    }
+
+    /// Same as `are_near` in `pyramid.wgsl`
+    fn are_near(&self, a: IVec2, b: IVec2, size: IVec2) -> bool {
+        let mut distance = (a - b).abs();
+        if self.boundary == BoundaryMode::Periodic {
+            distance = distance.min(size - distance);
+        }
+
+        distance.x <= 1 && distance.y <= 1
+    }
+
+    /// Same as `nearest_image` in `pyramid.wgsl`
+    fn nearest_image(&self, offset: Vec2) -> Vec2 {
+        if self.boundary != BoundaryMode::Periodic {
+            return offset;
+        }
+
+        let world = Vec2::new(self.size.0 as f32, self.size.1 as f32);
+        offset - world * (offset / world + 0.5).floor()
+    }
+
+    /// Same as `add_node_field` in `pyramid.wgsl`
+    fn add_node_field(&self, estimate: &mut FieldEstimate, node: PyramidNode, position: Vec2) {
+        if node.mass < EPSILON {
+            return;
+        }
+
+        let to_source = self.nearest_image(node.mass_moment / node.mass - position);
+        let source = GravityData {
+            to_source,
+            mass: node.mass,
+        };
+        estimate.add_field(source.field(), to_source.length());
+    }
+
+    /// Same as `get_pyramid_gravity_data` in `pyramid.wgsl`
+    fn get_pyramid_gravity_data(&self, current_cell: &CellData, current_pos: IVec2) -> GravityData {
+        let position = current_pos.as_vec2() + current_cell.relative_pos;
+        let top = self.pyramid_levels.len() - 1;
+        let mut estimate = FieldEstimate::default();
+
+        let base = self.pyramid_levels[0];
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let offset = IVec2::new(x, y);
+                if (x == 0 && y == 0) || self.is_repeated_offset(offset, base.size) {
+                    continue;
+                }
+
+                let neighbor = self.offset_node(current_pos, offset, base.size);
+                if is_in_level(neighbor, base.size) {
+                    self.add_node_field(&mut estimate, self.get_node(base, neighbor), position);
+                }
+            }
+        }
+
+        for level in 0..top {
+            let nodes = self.pyramid_levels[level];
+            let parents = self.pyramid_levels[level + 1];
+            let node = current_pos >> level as i32;
+            let parent = node >> 1;
+
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let offset = IVec2::new(x, y);
+                    if self.is_repeated_offset(offset, parents.size) {
+                        continue;
+                    }
+
+                    let neighbor = self.offset_node(parent, offset, parents.size);
+                    for child_y in 0..2 {
+                        for child_x in 0..2 {
+                            let child = neighbor * 2 + IVec2::new(child_x, child_y);
+                            if is_in_level(child, nodes.size)
+                                && !self.are_near(child, node, nodes.size)
+                            {
+                                let value = self.get_node(nodes, child);
+                                self.add_node_field(&mut estimate, value, position);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let nodes = self.pyramid_levels[top];
+        let node = current_pos >> top as i32;
+        for y in 0..nodes.size.y {
+            for x in 0..nodes.size.x {
+                let other = IVec2::new(x, y);
+                if !self.are_near(other, node, nodes.size) {
+                    self.add_node_field(&mut estimate, self.get_node(nodes, other), position);
+                }
+            }
+        }
+
+        if estimate.weight <= 0.0 {
+            return GravityData::NONE;
+        }
+
+        GravityData::from_field(estimate.field, estimate.weighted_distance / estimate.weight)
+    }
+
+    /// Same as `get_fft_gravity_data` in `fft.wgsl`
+    fn get_fft_gravity_data(&self, current_pos: IVec2) -> GravityData {
+        let field =
+            self.fft[fft_index(self.size, 0, current_pos)] / (self.size.0 * self.size.1) as f32;
+        GravityData::from_field(field, 1.0)
+    }
+
+    /// Iterates over all cell locations in the order of their indices.
+    fn locations(&self) -> impl Iterator<Item = IVec2> {
+        let (width, height) = self.size;
+        (0..height).flat_map(move |y| (0..width).map(move |x| IVec2::new(x as i32, y as i32)))
+    }
+
+    /// Same as `location_to_index` in `world_data.wgsl`
+    pub fn location_to_index(&self, location: IVec2) -> usize {
+        let width = self.size.0 as i32;
+        let height = self.size.1 as i32;
+        let looped = IVec2::new(location.x.rem_euclid(width), location.y.rem_euclid(height));
+        (looped.y * width + looped.x) as usize
+    }
+
+    /// Same as `is_out_of_bounds` in `utils.wgsl`
+    pub fn is_out_of_bounds(&self, location: IVec2) -> bool {
+        location.x < 0
+            || location.x >= self.size.0 as i32
+            || location.y < 0
+            || location.y >= self.size.1 as i32
+    }
+
+    /// Get cell data from previous frame, cells beyond the edges are empty
+    /// unless the world is periodic
+    pub fn get_prev_cell(&self, location: IVec2) -> CellData {
+        if self.boundary != BoundaryMode::Periodic && self.is_out_of_bounds(location) {
+            return CellData::new_empty();
+        }
+
+        self.data_prev[self.location_to_index(location)]
+    }
+
+    /// Set cell data for the next frame
+    pub fn set_next_cell(&mut self, location: IVec2, value: CellData) {
+        let index = self.location_to_index(location);
+        self.data_next[index] = value;
+    }
+}
+
+/// Same as `vec_to_neighbor` in `gravity_data.wgsl`
+fn vec_to_neighbor(
+    current_cell: &CellData,
+    current_pos: IVec2,
+    neighbor_pos: IVec2,
+    neighbor_cell: &CellData,
+) -> Vec2 {
+    (neighbor_pos - current_pos).as_vec2() + neighbor_cell.relative_pos - current_cell.relative_pos
+}
+
+/// Same as `cell_node` in `pyramid.wgsl`
+fn cell_node(cell: &CellData, location: IVec2) -> PyramidNode {
+    if cell.particle_type == PARTICLE_NOTHING {
+        return PyramidNode::default();
+    }
+
+    PyramidNode {
+        mass_moment: (location.as_vec2() + cell.relative_pos) * cell.mass,
+        mass: cell.mass,
+        ..default()
+    }
+}
+
+/// Same as `is_in_level` in `pyramid.wgsl`
+fn is_in_level(node: IVec2, size: IVec2) -> bool {
+    node.x >= 0 && node.x < size.x && node.y >= 0 && node.y < size.y
+}
+
+/// Same as `is_repeated_axis_offset` in `pyramid.wgsl`
+fn is_repeated_axis_offset(offset: i32, size: i32) -> bool {
+    (size == 1 && offset != 0) || (size == 2 && offset == 1)
+}
+
+/// Same as `approach_speed` in `collision.wgsl`
+fn approach_speed(first: &CellData, first_pos: IVec2, second: &CellData, second_pos: IVec2) -> f32 {
+    let normal = (second_pos - first_pos).as_vec2().normalize();
+    (first.impulse / first.mass - second.impulse / second.mass).dot(normal)
+}
+
+/// Same as `axis_to_dir` in `collision.wgsl`
+fn axis_to_dir(val: f32) -> i32 {
+    if val < -CELL_RADIUS {
+        -1
+    } else if val > CELL_RADIUS {
+        1
+    } else {
+        0
+    }
+}
+
+/// Same as `rel_pos_to_dir` in `collision.wgsl`
+fn rel_pos_to_dir(rel_pos: Vec2) -> IVec2 {
+    IVec2::new(axis_to_dir(rel_pos.x), axis_to_dir(rel_pos.y))
+}
+
+/// Same as `moves_to` in `collision.wgsl`
+fn moves_to(particle: &CellData, particle_pos: IVec2, location: IVec2) -> bool {
+    if particle.particle_type == PARTICLE_NOTHING {
+        return false;
+    }
+
+    particle_pos + rel_pos_to_dir(particle.relative_pos) == location
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::game_world::{SimulationStats, DEFAULT_MASS};
+
+    const SIZE: (u32, u32) = (64, 64);
+    const CENTER: IVec2 = IVec2::new(32, 32);
+
+    /// World with resting particles, gravity propagated for `steps`.
+    fn propagated_world(particles: &[(IVec2, f32)], steps: usize) -> CpuWorld {
+        let cells = vec![CellData::new_empty(); (SIZE.0 * SIZE.1) as usize];
+        let mut world = CpuWorld::from_cells(SIZE, cells);
+        for &(location, mass) in particles {
+            world.set_next_cell(location, CellData::new_particle(mass, Vec2::ZERO));
+        }
+        world.swap();
+
+        for _ in 0..steps {
+            world.pre_update();
+            world.update_gravity();
+            world.swap();
+        }
+
+        world
+    }
+
+    fn field_at(world: &CpuWorld, location: IVec2) -> Vec2 {
+        GravityData::of_cell(&world.get_prev_cell(location)).field()
+    }
+
+    /// Field of a point mass at `offset` from the cell
+    fn point_field(offset: IVec2, mass: f32) -> Vec2 {
+        GravityData {
+            to_source: offset.as_vec2(),
+            mass,
+        }
+        .field()
+    }
+
+    #[test]
+    fn single_mass_field_is_exact() {
+        let mass = 10.0;
+        let world = propagated_world(&[(CENTER, mass)], 24);
+
+        for y in -20..=20 {
+            for x in -20..=20 {
+                let offset = IVec2::new(x, y);
+                if offset == IVec2::ZERO {
+                    continue;
+                }
+
+                let expected = point_field(-offset, mass);
+                let field = field_at(&world, CENTER + offset);
+                assert!(
+                    (field - expected).length() < expected.length() * 1e-4,
+                    "offset {offset}: {field} != {expected}"
+                );
+            }
+        }
+
+        // a lone particle does not feel itself
+        assert_eq!(field_at(&world, CENTER), Vec2::ZERO);
+    }
+
+    #[test]
+    fn single_mass_field_is_radially_symmetric() {
+        let world = propagated_world(&[(CENTER, 3.0)], 24);
+
+        for y in 0..=20 {
+            for x in 1..=20 {
+                let field = field_at(&world, CENTER + IVec2::new(x, y));
+
+                // rotations by 90 degrees
+                for rotation in [IVec2::new(0, 1), IVec2::new(-1, 0), IVec2::new(0, -1)] {
+                    let rotated = rotation.rotate(IVec2::new(x, y));
+                    let expected = rotation.as_vec2().rotate(field);
+                    let rotated_field = field_at(&world, CENTER + rotated);
+                    assert!(
+                        (rotated_field - expected).length() < field.length() * 1e-4,
+                        "offset {rotated}: {rotated_field} != {expected}"
+                    );
+                }
+
+                // field points to the mass
+                let to_mass = -IVec2::new(x, y).as_vec2().normalize();
+                assert!(field.normalize().dot(to_mass) > 1.0 - 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn field_strength_is_inverse_square_and_proportional_to_mass() {
+        let light = propagated_world(&[(CENTER, 1.0)], 24);
+        let heavy = propagated_world(&[(CENTER, 4.0)], 24);
+
+        let near = field_at(&light, CENTER + IVec2::new(5, 0)).length();
+        let far = field_at(&light, CENTER + IVec2::new(10, 0)).length();
+        assert!((near / far - 4.0).abs() < 1e-3, "{near} / {far}");
+
+        let heavy_near = field_at(&heavy, CENTER + IVec2::new(5, 0)).length();
+        assert!(
+            (heavy_near / near - 4.0).abs() < 1e-3,
+            "{heavy_near} / {near}"
+        );
+    }
+
+    #[test]
+    fn field_propagates_one_cell_per_step() {
+        let world = propagated_world(&[(CENTER, 1.0)], 5);
+
+        assert_ne!(field_at(&world, CENTER + IVec2::new(5, 5)), Vec2::ZERO);
+        assert_eq!(field_at(&world, CENTER + IVec2::new(6, 0)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn equal_masses_attract_each_other() {
+        let left = CENTER - IVec2::new(4, 0);
+        let right = CENTER + IVec2::new(4, 0);
+        let world = propagated_world(&[(left, 1.0), (right, 1.0)], 32);
+
+        let left_field = field_at(&world, left);
+        let right_field = field_at(&world, right);
+        assert!(left_field.x > 0.0, "{left_field}");
+        assert!((left_field + right_field).length() < left_field.length() * 1e-4);
+    }
+
+    /// Particle moving by `direction` during the next `update_position`
+    fn moving_particle(mass: f32, direction: IVec2) -> CellData {
+        CellData {
+            relative_pos: direction.as_vec2() * 0.8,
+            impulse: direction.as_vec2() * mass,
+            ..CellData::new_particle(mass, Vec2::ZERO)
+        }
+    }
+
+    fn world_with_cells(cells: &[(IVec2, CellData)]) -> CpuWorld {
+        let empty = vec![CellData::new_empty(); (SIZE.0 * SIZE.1) as usize];
+        let mut world = CpuWorld::from_cells(SIZE, empty);
+        for &(location, cell) in cells {
+            world.set_next_cell(location, cell);
+        }
+        world.swap();
+        world
+    }
+
+    fn particles(world: &CpuWorld) -> Vec<CellData> {
+        world
+            .data_prev()
+            .iter()
+            .filter(|cell| cell.particle_type != PARTICLE_NOTHING)
+            .copied()
+            .collect()
+    }
+
+    fn total_mass(world: &CpuWorld) -> f32 {
+        particles(world).iter().map(|cell| cell.mass).sum()
+    }
+
+    #[test]
+    fn init_is_deterministic_for_a_seed() {
+        let init = |seed: u32, particle_chance: f32| {
+            let mut world = CpuWorld::new(SIZE);
+            world.params.seed = seed;
+            world.params.particle_chance = particle_chance;
+            world.run_init();
+            world.data_prev().to_vec()
+        };
+
+        assert_eq!(init(1, 0.2), init(1, 0.2));
+        assert_ne!(init(1, 0.2), init(2, 0.2));
+
+        let count = |cells: &[CellData]| {
+            cells
+                .iter()
+                .filter(|cell| cell.particle_type != PARTICLE_NOTHING)
+                .count()
+        };
+        assert_eq!(count(&init(1, 0.0)), 0);
+        let full = init(1, 1.0);
+        assert_eq!(count(&full), full.len());
+        assert!(full
+            .iter()
+            .all(|cell| *cell == CellData::new_particle(DEFAULT_MASS, Vec2::ZERO)));
+    }
+
+    #[test]
+    fn pre_update_copies_the_previous_state() {
+        let mut world = world_with_cells(&[(CENTER, moving_particle(2.0, IVec2::X))]);
+        world.set_next_cell(CENTER + IVec2::Y, moving_particle(4.0, IVec2::Y));
+
+        world.pre_update();
+
+        assert_eq!(world.data_next(), world.data_prev());
+    }
+
+    #[test]
+    fn impulse_grows_along_the_field() {
+        let mass = 2.0;
+        let mut world = world_with_cells(&[(
+            CENTER,
+            CellData {
+                to_gravity_source: Vec2::new(3.0, 4.0),
+                gravity_strength: 25.0,
+                ..CellData::new_particle(mass, Vec2::new(-0.1, 0.0))
+            },
+        )]);
+        world.params.step_duration = 0.5;
+        world.params.gravity_constant = 2.0;
+        world.params.max_speed = 10.0;
+
+        world.pre_update();
+        world.update_impulse();
+        world.swap();
+
+        // unit field towards the source
+        let expected = Vec2::new(-0.2, 0.0) + Vec2::new(0.6, 0.8) * mass * 2.0 * 0.5;
+        let cell = world.get_prev_cell(CENTER);
+        assert!(
+            (cell.impulse - expected).length() < 1e-6,
+            "{}",
+            cell.impulse
+        );
+        assert!((cell.relative_pos - expected / mass * 0.5).length() < 1e-6);
+    }
+
+    #[test]
+    fn movement_is_capped_by_max_speed() {
+        let mut world = world_with_cells(&[
+            (CENTER, CellData::new_particle(1.0, Vec2::new(100.0, 0.0))),
+            (CENTER + IVec2::Y, CellData::new_empty()),
+        ]);
+        world.params.step_duration = 1.0;
+        world.params.gravity_constant = 0.0;
+        world.params.max_speed = 0.9;
+
+        world.pre_update();
+        world.update_impulse();
+        world.swap();
+
+        let cell = world.get_prev_cell(CENTER);
+        assert_eq!(cell.relative_pos, Vec2::new(0.9, 0.0));
+        assert_eq!(cell.impulse, Vec2::new(100.0, 0.0));
+        // empty cells don't move
+        assert_eq!(
+            world.get_prev_cell(CENTER + IVec2::Y),
+            CellData::new_empty()
+        );
+    }
+
+    #[test]
+    fn particle_moves_to_the_neighbor_cell() {
+        let particle = moving_particle(2.0, IVec2::new(1, -1));
+        let mut world = world_with_cells(&[(CENTER, particle)]);
+
+        world.pre_update();
+        world.update_position();
+        world.swap();
+
+        let moved = world.get_prev_cell(CENTER + IVec2::new(1, -1));
+        assert_eq!(moved.mass, 2.0);
+        assert_eq!(moved.impulse, particle.impulse);
+        assert_eq!(moved.relative_pos, CELL_CENTER);
+        assert_eq!(world.get_prev_cell(CENTER).particle_type, PARTICLE_NOTHING);
+        assert_eq!(particles(&world).len(), 1);
+    }
+
+    #[test]
+    fn particles_moving_to_the_same_cell_merge() {
+        let mut world = world_with_cells(&[
+            (CENTER - IVec2::X, moving_particle(1.0, IVec2::X)),
+            (CENTER + IVec2::X, moving_particle(2.0, -IVec2::X)),
+            (CENTER + IVec2::ONE, moving_particle(4.0, -IVec2::ONE)),
+            (CENTER, moving_particle(8.0, IVec2::ZERO)),
+        ]);
+
+        world.pre_update();
+        world.update_position();
+        world.swap();
+
+        let particles = particles(&world);
+        assert_eq!(particles.len(), 1);
+        let merged = world.get_prev_cell(CENTER);
+        assert_eq!(merged.mass, 15.0);
+        assert_eq!(merged.impulse, Vec2::new(-5.0, -4.0));
+    }
+
+    #[test]
+    fn particle_leaving_a_cell_does_not_take_arriving_particle() {
+        // a chain of particles moving in the same direction and a pair
+        // swapping their cells
+        let mut world = world_with_cells(&[
+            (CENTER, moving_particle(1.0, IVec2::X)),
+            (CENTER + IVec2::X, moving_particle(2.0, IVec2::X)),
+            (CENTER + IVec2::new(2, 0), moving_particle(4.0, IVec2::X)),
+            (CENTER + IVec2::Y, moving_particle(8.0, IVec2::Y)),
+            (CENTER + IVec2::new(0, 2), moving_particle(16.0, -IVec2::Y)),
+        ]);
+
+        world.pre_update();
+        world.update_position();
+        world.swap();
+
+        assert_eq!(world.get_prev_cell(CENTER).particle_type, PARTICLE_NOTHING);
+        assert_eq!(world.get_prev_cell(CENTER + IVec2::X).mass, 1.0);
+        assert_eq!(world.get_prev_cell(CENTER + IVec2::new(2, 0)).mass, 2.0);
+        assert_eq!(world.get_prev_cell(CENTER + IVec2::new(3, 0)).mass, 4.0);
+        assert_eq!(world.get_prev_cell(CENTER + IVec2::new(0, 2)).mass, 8.0);
+        assert_eq!(world.get_prev_cell(CENTER + IVec2::Y).mass, 16.0);
+        assert_eq!(particles(&world).len(), 5);
+    }
+
+    #[test]
+    fn mass_is_conserved_every_step() {
+        let mut world = CpuWorld::new(SIZE);
+        world.params.particle_chance = 0.2;
+        world.run_init();
+
+        // unit masses keep sums exact
+        let initial_mass = total_mass(&world);
+        let mut particle_count = particles(&world).len();
+        assert!(particle_count > 0);
+        assert_eq!(initial_mass, particle_count as f32);
+
+        for _ in 0..64 {
+            world.step();
+
+            let count = particles(&world).len();
+            assert!(count <= particle_count);
+            particle_count = count;
+            assert_eq!(total_mass(&world), initial_mass);
+        }
+    }
+
+    #[test]
+    fn mass_and_momentum_are_conserved_with_bounces() {
+        let mut world = CpuWorld::new(SIZE);
+        world.collisions = CollisionMode::Bounce;
+        // pulls every pair of particles equally, unlike the relay
+        world.gravity = GravityMode::Fft;
+        world.params.particle_chance = 0.2;
+        world.run_init();
+
+        let initial_mass = total_mass(&world);
+
+        for step in 0..64 {
+            world.step();
+
+            assert_eq!(total_mass(&world), initial_mass, "step {step}");
+            let particles = particles(&world);
+            let momentum: Vec2 = particles.iter().map(|cell| cell.impulse).sum();
+            let scale: f32 = particles.iter().map(|cell| cell.impulse.length()).sum();
+            assert!(
+                momentum.length() <= scale * 1e-4,
+                "step {step}: {momentum} of {scale}"
+            );
+        }
+    }
+
+    /// World with bouncing collisions after a single position pass.
+    fn collided_world(restitution: f32, merge_speed: f32, cells: &[(IVec2, CellData)]) -> CpuWorld {
+        let mut world = world_with_cells(cells);
+        world.collisions = CollisionMode::Bounce;
+        world.params.restitution = restitution;
+        world.params.merge_speed = merge_speed;
+
+        world.pre_update();
+        world.update_position();
+        world.swap();
+
+        world
+    }
+
+    #[test]
+    fn fast_particles_bounce_off_each_other() {
+        let world = collided_world(
+            1.0,
+            0.5,
+            &[
+                (CENTER - IVec2::X, moving_particle(1.0, IVec2::X)),
+                (CENTER + IVec2::X, moving_particle(1.0, -IVec2::X)),
+            ],
+        );
+
+        // equal masses exchange their velocities and stay in their cells
+        assert_eq!(world.get_prev_cell(CENTER).particle_type, PARTICLE_NOTHING);
+        let left = world.get_prev_cell(CENTER - IVec2::X);
+        let right = world.get_prev_cell(CENTER + IVec2::X);
+        assert_eq!(left.impulse, Vec2::new(-1.0, 0.0));
+        assert_eq!(right.impulse, Vec2::new(1.0, 0.0));
+        assert_eq!(left.relative_pos, CELL_CENTER);
+        assert_eq!(particles(&world).len(), 2);
+    }
+
+    #[test]
+    fn slow_particles_merge() {
+        let world = collided_world(
+            1.0,
+            3.0,
+            &[
+                (CENTER - IVec2::X, moving_particle(1.0, IVec2::X)),
+                (CENTER + IVec2::X, moving_particle(1.0, -IVec2::X)),
+            ],
+        );
+
+        let merged = world.get_prev_cell(CENTER);
+        assert_eq!(merged.mass, 2.0);
+        assert_eq!(merged.impulse, Vec2::ZERO);
+        assert_eq!(particles(&world).len(), 1);
+    }
+
+    #[test]
+    fn inelastic_bounce_moves_particles_together() {
+        let world = collided_world(
+            0.0,
+            0.5,
+            &[
+                (CENTER - IVec2::ONE, moving_particle(1.0, IVec2::ONE)),
+                (CENTER, moving_particle(3.0, IVec2::ZERO)),
+            ],
+        );
+
+        // no approach speed is left along the diagonal
+        let moving = world.get_prev_cell(CENTER - IVec2::ONE);
+        let resting = world.get_prev_cell(CENTER);
+        let velocity = |cell: CellData| cell.impulse / cell.mass;
+        assert!((velocity(moving) - velocity(resting)).length() < 1e-6);
+        assert!((moving.impulse + resting.impulse - Vec2::ONE).length() < 1e-6);
+        assert_eq!(particles(&world).len(), 2);
+    }
+
+    #[test]
+    fn particle_following_a_bounced_one_merges_with_it() {
+        let world = collided_world(
+            1.0,
+            0.5,
+            &[
+                (CENTER - IVec2::new(2, 0), moving_particle(1.0, IVec2::X)),
+                (CENTER - IVec2::X, moving_particle(2.0, IVec2::X)),
+                (CENTER, moving_particle(4.0, IVec2::ZERO)),
+            ],
+        );
+
+        let merged = world.get_prev_cell(CENTER - IVec2::X);
+        assert_eq!(merged.mass, 3.0);
+        assert_eq!(world.get_prev_cell(CENTER).mass, 4.0);
+        assert_eq!(total_mass(&world), 7.0);
+        let momentum: Vec2 = particles(&world).iter().map(|cell| cell.impulse).sum();
+        assert!((momentum - Vec2::new(3.0, 0.0)).length() < 1e-6);
+    }
+
+    /// Runs a full step with a single particle leaving the world through the
+    /// left edge.
+    fn step_particle_at_edge(boundary: BoundaryMode) -> CpuWorld {
+        let start = IVec2::new(0, CENTER.y);
+        let mut world = world_with_cells(&[(start, CellData::new_particle(2.0, -Vec2::X))]);
+        world.boundary = boundary;
+        world.step();
+        world
+    }
+
+    #[test]
+    fn periodic_edges_wrap_particles_around() {
+        let world = step_particle_at_edge(BoundaryMode::Periodic);
+
+        let wrapped = world.get_prev_cell(IVec2::new(SIZE.0 as i32 - 1, CENTER.y));
+        assert_eq!(wrapped.mass, 2.0);
+        assert_eq!(wrapped.impulse, Vec2::new(-2.0, 0.0));
+    }
+
+    #[test]
+    fn reflecting_edges_bounce_particles_back() {
+        let world = step_particle_at_edge(BoundaryMode::Reflect);
+
+        let bounced = world.get_prev_cell(IVec2::new(1, CENTER.y));
+        assert_eq!(bounced.mass, 2.0);
+        assert_eq!(bounced.impulse, Vec2::new(2.0, 0.0));
+        assert_eq!(particles(&world).len(), 1);
+    }
+
+    #[test]
+    fn absorbing_edges_report_lost_mass() {
+        let world = step_particle_at_edge(BoundaryMode::Absorb);
+
+        assert!(particles(&world).is_empty());
+        let stats = SimulationStats::from_cells(1, SIZE, world.data_prev(), world.lost_mass());
+        assert_eq!(stats.total_mass, 0.0);
+        assert_eq!(stats.lost_mass, 2.0);
+    }
+
+    #[test]
+    fn gravity_crosses_only_periodic_edges() {
+        for boundary in [
+            BoundaryMode::Periodic,
+            BoundaryMode::Reflect,
+            BoundaryMode::Absorb,
+        ] {
+            let mut world =
+                world_with_cells(&[(IVec2::new(1, CENTER.y), moving_particle(1.0, IVec2::ZERO))]);
+            world.boundary = boundary;
+            for _ in 0..4 {
+                world.pre_update();
+                world.update_gravity();
+                world.swap();
+            }
+
+            let across_edge = field_at(&world, IVec2::new(SIZE.0 as i32 - 1, CENTER.y));
+            assert_eq!(
+                across_edge != Vec2::ZERO,
+                boundary == BoundaryMode::Periodic,
+                "{boundary}: {across_edge}"
+            );
+        }
+    }
+
+    /// World with resting particles after a single step of the gravity
+    /// passes.
+    fn gravity_world(
+        size: (u32, u32),
+        boundary: BoundaryMode,
+        gravity: GravityMode,
+        particles: &[(IVec2, f32)],
+    ) -> CpuWorld {
+        let cells = vec![CellData::new_empty(); (size.0 * size.1) as usize];
+        let mut world = CpuWorld::from_cells(size, cells);
+        world.boundary = boundary;
+        world.gravity = gravity;
+        for &(location, mass) in particles {
+            world.set_next_cell(location, CellData::new_particle(mass, Vec2::ZERO));
+        }
+        world.swap();
+
+        world.pre_update();
+        world.swap();
+        match gravity {
+            GravityMode::Relay => {}
+            GravityMode::Pyramid => world.build_pyramid_levels(),
+            GravityMode::Fft => world.run_fft_passes(),
+        }
+        world.update_gravity();
+        world.swap();
+
+        world
+    }
+
+    /// Particles of various masses clustered in the corner of the world, a
+    /// particle per cell.
+    fn clustered_particles() -> Vec<(IVec2, f32)> {
+        let mut particles: Vec<_> = (0..40)
+            .map(|i| {
+                let location = IVec2::new(
+                    (random_float_seeded(1, i) * 24.0) as i32 + 8,
+                    (random_float_seeded(2, i) * 24.0) as i32 + 8,
+                );
+                (location, 1.0 + random_float_seeded(3, i) * 9.0)
+            })
+            .collect();
+        particles.sort_by_key(|(location, _)| (location.x, location.y));
+        particles.dedup_by_key(|(location, _)| *location);
+        particles
+    }
+
+    /// Field of all particles except the one in the cell, summed one by one
+    fn direct_field(world: &CpuWorld, location: IVec2, particles: &[(IVec2, f32)]) -> Vec2 {
+        particles
+            .iter()
+            .filter(|(particle, _)| *particle != location)
+            .map(|&(particle, mass)| {
+                let to_source = world.nearest_image((particle - location).as_vec2());
+                GravityData { to_source, mass }.field()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn pyramid_field_of_single_mass_is_exact_everywhere() {
+        for size in [SIZE, (37, 23)] {
+            for boundary in [BoundaryMode::Periodic, BoundaryMode::Absorb] {
+                let (width, height) = (size.0 as i32, size.1 as i32);
+                for source in [
+                    IVec2::new(width / 2, height / 2),
+                    IVec2::ZERO,
+                    IVec2::new(width - 1, 1),
+                ] {
+                    let particles = [(source, 10.0)];
+                    // unlike the relay, the field reaches the whole world in a single step
+                    let world = gravity_world(size, boundary, GravityMode::Pyramid, &particles);
+
+                    for location in world.locations() {
+                        let expected = direct_field(&world, location, &particles);
+                        let field = field_at(&world, location);
+                        assert!(
+                            (field - expected).length() <= expected.length() * 1e-4,
+                            "{size:?} {boundary} {source} at {location}: {field} != {expected}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pyramid_field_approximates_direct_sum() {
+        let particles = clustered_particles();
+
+        for boundary in [BoundaryMode::Periodic, BoundaryMode::Absorb] {
+            let world = gravity_world(SIZE, boundary, GravityMode::Pyramid, &particles);
+            let fields: Vec<_> = world
+                .locations()
+                .map(|location| {
+                    let expected = direct_field(&world, location, &particles);
+                    (field_at(&world, location), expected)
+                })
+                .collect();
+
+            // relative to the typical strength, the field is weak where it
+            // cancels out and nearest images of distant nodes flip there
+            let cells = fields.len() as f32;
+            let strength = fields
+                .iter()
+                .map(|(_, expected)| expected.length())
+                .sum::<f32>()
+                / cells;
+            let errors: Vec<_> = fields
+                .iter()
+                .map(|(field, expected)| (*field - *expected).length() / strength)
+                .collect();
+
+            let max_error = errors.iter().copied().fold(0.0, f32::max);
+            let mean_error = errors.iter().sum::<f32>() / cells;
+            assert!(max_error < 0.25, "{boundary}: max error {max_error}");
+            assert!(mean_error < 0.05, "{boundary}: mean error {mean_error}");
+        }
+    }
+
+    #[test]
+    fn fft_field_of_single_mass_falls_off_with_distance() {
+        for source in [CENTER, IVec2::ZERO, IVec2::new(SIZE.0 as i32 - 1, 5)] {
+            let particles = [(source, 10.0)];
+            let world = gravity_world(SIZE, BoundaryMode::Periodic, GravityMode::Fft, &particles);
+
+            assert!(field_at(&world, source).length() < 1e-5);
+            for location in world.locations() {
+                let to_source = world.nearest_image((source - location).as_vec2());
+                let distance = to_source.length();
+                if !(3.0..=8.0).contains(&distance) {
+                    continue;
+                }
+
+                // field of a mass in the plane is `mass / distance`, weakened by
+                // the mean density and the images farther away
+                let expected = to_source / distance * 10.0 / distance;
+                let field = field_at(&world, location);
+                assert!(
+                    (field - expected).length() <= expected.length() * 0.1,
+                    "{source} at {location}: {field} != {expected}"
+                );
+            }
+        }
+    }
+
+    /// Field of a unit mass at every offset from it, the inverse transform of
+    /// [`fft_kernel_spectrum`] summed frequency by frequency.
+    fn fft_unit_field(size: (u32, u32)) -> Vec<Vec2> {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let twiddle = |frequency: usize, offset: usize, length: usize| {
+            let angle = 2.0 * PI * (frequency * offset % length) as f32 / length as f32;
+            Vec2::new(angle.cos(), angle.sin())
+        };
+
+        // along the rows and then along the columns
+        let spectrum = fft_kernel_spectrum(GameWorldSize(size));
+        let mut rows = vec![Vec2::ZERO; width * height];
+        for frequency_y in 0..height {
+            for x in 0..width {
+                rows[frequency_y * width + x] = (0..width)
+                    .map(|frequency_x| {
+                        complex_mul(
+                            spectrum[frequency_y * width + frequency_x],
+                            twiddle(frequency_x, x, width),
+                        )
+                    })
+                    .sum();
+            }
+        }
+
+        let mut field = vec![Vec2::ZERO; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                field[y * width + x] = (0..height)
+                    .map(|frequency_y| {
+                        complex_mul(
+                            rows[frequency_y * width + x],
+                            twiddle(frequency_y, y, height),
+                        )
+                    })
+                    .sum::<Vec2>()
+                    / (width * height) as f32;
+            }
+        }
+        field
+    }
+
+    #[test]
+    fn fft_field_matches_direct_sum() {
+        let particles = clustered_particles();
+        let world = gravity_world(SIZE, BoundaryMode::Periodic, GravityMode::Fft, &particles);
+        let unit_field = fft_unit_field(SIZE);
+
+        for location in world.locations() {
+            // same solution summed particle by particle
+            let expected: Vec2 = particles
+                .iter()
+                .map(|&(particle, mass)| {
+                    unit_field[world.location_to_index(location - particle)] * mass
+                })
+                .sum();
+            let field = field_at(&world, location);
+            assert!(
+                (field - expected).length() <= expected.length() * 1e-3 + 1e-4,
+                "at {location}: {field} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn fft_forces_cancel_out() {
+        let particles = clustered_particles();
+        let world = gravity_world(SIZE, BoundaryMode::Periodic, GravityMode::Fft, &particles);
+
+        let mut total_force = Vec2::ZERO;
+        let mut max_force = 0.0_f32;
+        for &(location, mass) in &particles {
+            let force = field_at(&world, location) * mass;
+            total_force += force;
+            max_force = max_force.max(force.length());
+        }
+
+        assert!(
+            total_force.length() < max_force * 1e-4,
+            "{total_force} of {max_force}"
+        );
+    }
+}
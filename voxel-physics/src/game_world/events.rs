@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::game_world::SimulationStats;
+
+/// Reads the current world state back from the GPU and saves it to the file.
+#[derive(Clone, Debug, Event)]
+pub struct SaveWorldSnapshot(pub PathBuf);
+
+/// Loads the world state from the file into both storage buffers.
+#[derive(Clone, Debug, Event)]
+pub struct LoadWorldSnapshot(pub PathBuf);
+
+/// Sent once a snapshot requested by [`SaveWorldSnapshot`] is written.
+#[derive(Clone, Debug, Event)]
+pub struct WorldSnapshotSaved {
+    pub path: PathBuf,
+    pub step: u64,
+}
+
+/// Sent when a snapshot requested by [`SaveWorldSnapshot`], or a frame
+/// requested by [`SaveWorldFrame`] or recorded by
+/// [`FrameRecording`](crate::game_world::FrameRecording), can't be read back
+/// from the GPU or written, or when a scenario requested by [`LoadScenario`]
+/// can't be loaded.
+#[derive(Clone, Debug, Event)]
+pub struct WorldFileFailed {
+    pub path: PathBuf,
+    /// Error message
+    pub message: String,
+}
+
+/// Reads the world texture back from the GPU and saves it as a PNG image.
+#[derive(Clone, Debug, Event)]
+pub struct SaveWorldFrame(pub PathBuf);
+
+/// Sent once a frame requested by [`SaveWorldFrame`] or recorded by
+/// [`FrameRecording`](crate::game_world::FrameRecording) is written.
+#[derive(Clone, Debug, Event)]
+pub struct WorldFrameSaved {
+    pub path: PathBuf,
+    pub step: u64,
+}
+
+/// Sent for every simulation step once its stats are read back, in the order
+/// of steps.
+#[derive(Clone, Debug, Event)]
+pub struct SimulationStatsUpdated(pub SimulationStats);
+
+/// Sent when a pipeline of the world fails to compile, or when the
+/// [`GravityMode`](crate::game_world::GravityMode) doesn't support the world,
+/// the simulation doesn't start until the shader is fixed.
+#[derive(Clone, Debug, PartialEq, Eq, Event)]
+pub struct PipelineCompilationFailed {
+    /// Entry point of the pipeline in `game_world.wgsl`
+    pub entry_point: String,
+    /// Shader error message
+    pub message: String,
+}
+
+/// Resets the world by re-running `init` pass, or by uploading the loaded
+/// scenario again, the step counter starts over.
+#[derive(Clone, Debug, Default, Event)]
+pub struct ReinitializeWorld {
+    /// New [`SimulationParams::seed`](crate::game_world::SimulationParams),
+    /// the current one is kept if `None`.
+    pub seed: Option<u32>,
+}
+
+/// Loads the scenario from the file and uploads it instead of the current
+/// world state.
+#[derive(Clone, Debug, Event)]
+pub struct LoadScenario(pub PathBuf);
@@ -0,0 +1,38 @@
+//! Frames of the world texture exported as PNG images, either single ones
+//! requested with [`SaveWorldFrame`](crate::game_world::SaveWorldFrame) or
+//! numbered sequences written while
+//! [`FrameRecording`](crate::game_world::FrameRecording) is enabled.
+
+use std::{fs, path::Path};
+
+use image::{ColorType, ImageFormat, ImageResult};
+
+/// The world texture at some simulation step in its native resolution, one
+/// pixel per cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldFrame {
+    pub size: (u32, u32),
+    pub step: u64,
+    /// RGBA pixels row by row starting from the top
+    pub pixels: Vec<u8>,
+}
+
+impl WorldFrame {
+    /// Saves the frame as a PNG image, missing parent directories are
+    /// created.
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        image::save_buffer_with_format(
+            path,
+            &self.pixels,
+            self.size.0,
+            self.size.1,
+            ColorType::Rgba8,
+            ImageFormat::Png,
+        )
+    }
+}
@@ -0,0 +1,109 @@
+//! Initial conditions drawn in an image editor.
+//!
+//! Every pixel of a mass map becomes a particle in the cell with the same
+//! coordinates, rows go from top to bottom like in the world texture:
+//!
+//! - without velocity, the mass is proportional to the luminance of the pixel,
+//!   so both grayscale and color images work.
+//! - with velocity, the blue channel holds the mass while the red and green
+//!   channels hold the x and y velocity, `128` being zero, `255` the max speed
+//!   and `0` the max speed in the opposite direction.
+//!
+//! The mass is multiplied by the alpha channel in both cases, pixels without
+//! mass stay empty. Images with more than 8 bits per channel are rounded to 8
+//! bits.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use image::{ImageResult, RgbaImage};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MassMap(pub RgbaImage);
+
+impl MassMap {
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Ok(Self(image::open(path)?.into_rgba8()))
+    }
+
+    /// Location, mass and velocity of the particle of every pixel with mass.
+    ///
+    /// `mass` is the mass of a white pixel, velocity is encoded only if
+    /// `max_speed` is positive.
+    pub fn particles(
+        &self,
+        mass: f32,
+        max_speed: f32,
+    ) -> impl Iterator<Item = (IVec2, f32, Vec2)> + '_ {
+        self.0.enumerate_pixels().filter_map(move |(x, y, pixel)| {
+            let [red, green, blue, alpha] = pixel.0;
+
+            let (intensity, velocity) = if max_speed > 0.0 {
+                let velocity = Vec2::new(signed_channel(red), signed_channel(green));
+                (blue as f32 / 255.0, velocity * max_speed)
+            } else {
+                // Rec. 709 luma, in integers so that white is exactly one
+                let luma = 2126 * red as u32 + 7152 * green as u32 + 722 * blue as u32;
+                (luma as f32 / (10000.0 * 255.0), Vec2::ZERO)
+            };
+
+            let particle_mass = intensity * (alpha as f32 / 255.0) * mass;
+            (particle_mass > 0.0).then(|| (IVec2::new(x as i32, y as i32), particle_mass, velocity))
+        })
+    }
+}
+
+/// Maps the channel to `[-1, 1]` with `128` being zero.
+fn signed_channel(channel: u8) -> f32 {
+    ((channel as f32 - 128.0) / 127.0).max(-1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn mass_map(pixels: &[[u8; 4]]) -> MassMap {
+        let mut image = RgbaImage::new(pixels.len() as u32, 1);
+        for (x, pixel) in pixels.iter().enumerate() {
+            image.put_pixel(x as u32, 0, Rgba(*pixel));
+        }
+        MassMap(image)
+    }
+
+    #[test]
+    fn luminance_maps_to_mass() {
+        let map = mass_map(&[
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+            [255, 255, 255, 0],
+            [255, 255, 255, 51],
+        ]);
+
+        let particles: Vec<_> = map.particles(2.0, 0.0).collect();
+
+        assert_eq!(
+            particles,
+            [
+                (IVec2::new(0, 0), 2.0, Vec2::ZERO),
+                (IVec2::new(3, 0), 0.4, Vec2::ZERO),
+            ]
+        );
+    }
+
+    #[test]
+    fn red_and_green_map_to_velocity() {
+        let map = mass_map(&[[255, 128, 255, 255], [0, 255, 51, 255], [128, 128, 0, 255]]);
+
+        let particles: Vec<_> = map.particles(1.0, 0.5).collect();
+
+        assert_eq!(
+            particles,
+            [
+                (IVec2::new(0, 0), 1.0, Vec2::new(0.5, 0.0)),
+                (IVec2::new(1, 0), 0.2, Vec2::new(-0.5, 0.5)),
+            ]
+        );
+    }
+}
@@ -1,13 +1,18 @@
+use std::path::PathBuf;
+
 use bevy::prelude::*;
 use bevy::render::extract_resource::ExtractResourcePlugin;
 use bevy::render::main_graph::node::CAMERA_DRIVER;
 use bevy::render::render_graph::RenderGraph;
+use bevy::render::ExtractSchedule;
 use bevy::render::Render;
 use bevy::render::RenderApp;
 use bevy::render::RenderSet;
+use bevy_inspector_egui::bevy_egui::EguiPlugin;
 
 pub use components::*;
 pub use constants::*;
+pub use events::*;
 use render::GameWorldNode;
 pub use resources::*;
 use systems::*;
@@ -16,30 +21,175 @@ use crate::utils::add_resource::AddAndRegisterRes;
 
 pub mod components;
 pub mod constants;
+pub mod cpu;
+mod events;
+pub mod frame;
+pub mod mass_map;
 mod render;
 mod resources;
+pub mod scenario;
+pub mod scheduler;
+pub mod snapshot;
 mod systems;
 
-#[derive(Clone, Debug, Default)]
-pub struct GameWorldPlugin;
+/// Simulation of the world on the GPU, doesn't require a window.
+#[derive(Clone, Debug)]
+pub struct GameWorldPlugin {
+    /// Size of the world in cells, doesn't have to be a multiple of
+    /// [`WORKGROUP_SIZE`].
+    pub world_size: (u32, u32),
+    /// Initial [`SimulationParams::seed`], can be changed later with
+    /// [`ReinitializeWorld`].
+    pub seed: u32,
+    /// Scenario file loaded instead of running `init` pass.
+    pub scenario: Option<PathBuf>,
+    /// What happens at the edges of the world.
+    pub boundary: BoundaryMode,
+    /// How the field of the particles is computed, a mode which doesn't
+    /// support the world fails as [`PipelineCompilationFailed`].
+    pub gravity: GravityMode,
+    /// What happens to particles moving to the same cell.
+    pub collisions: CollisionMode,
+}
+
+impl Default for GameWorldPlugin {
+    fn default() -> Self {
+        Self {
+            world_size: DEFAULT_WORLD_SIZE,
+            seed: DEFAULT_SEED,
+            scenario: None,
+            boundary: BoundaryMode::default(),
+            gravity: GravityMode::default(),
+            collisions: CollisionMode::default(),
+        }
+    }
+}
 
 impl Plugin for GameWorldPlugin {
     fn build(&self, app: &mut App) {
+        let world_size = GameWorldSize(self.world_size);
+
+        let (snapshot_sender, snapshot_receiver) = crossbeam_channel::unbounded();
+        let (progress_sender, progress_receiver) = crossbeam_channel::unbounded();
+        let (stats_sender, stats_receiver) = crossbeam_channel::unbounded();
+        let (inspector_sender, inspector_receiver) = crossbeam_channel::unbounded();
+        let (frame_sender, frame_receiver) = crossbeam_channel::unbounded();
+        let (pipeline_errors_sender, pipeline_errors_receiver) = crossbeam_channel::unbounded();
+
         app.add_systems(Startup, world_init_sys);
-        app.add_systems(Update, world_control_sys);
+        app.add_systems(
+            First,
+            (
+                reset_single_step_sys,
+                receive_progress_sys,
+                receive_stats_sys,
+                receive_inspector_sys,
+                receive_pipeline_errors_sys,
+            ),
+        );
+        app.add_systems(Last, tick_simulation_clock_sys);
+        app.add_systems(
+            Update,
+            (
+                save_world_snapshot_sys,
+                load_world_snapshot_sys,
+                write_world_snapshot_sys,
+                save_world_frame_sys,
+                write_world_frame_sys,
+                reinitialize_world_sys,
+                load_scenario_sys,
+            ),
+        );
 
-        app.register_type::<WorldSprite>();
+        app.add_event::<SaveWorldSnapshot>()
+            .add_event::<LoadWorldSnapshot>()
+            .add_event::<WorldSnapshotSaved>()
+            .add_event::<WorldFileFailed>()
+            .add_event::<SaveWorldFrame>()
+            .add_event::<WorldFrameSaved>()
+            .add_event::<SimulationStatsUpdated>()
+            .add_event::<PipelineCompilationFailed>()
+            .add_event::<ReinitializeWorld>()
+            .add_event::<LoadScenario>();
 
-        app.init_and_register_res::<GameWorldViewportScale>()
-            .init_and_register_res::<GameWorldSensitivity>();
+        if let Some(path) = &self.scenario {
+            app.world.send_event(LoadScenario(path.clone()));
+        }
+
+        app.insert_and_register_res(world_size)
+            .insert_and_register_res(self.boundary)
+            .insert_and_register_res(self.gravity)
+            .insert_and_register_res(self.collisions)
+            .insert_and_register_res(SimulationParams {
+                seed: self.seed,
+                ..default()
+            })
+            .init_and_register_res::<SimulationControl>()
+            .init_and_register_res::<SimulationClock>()
+            .init_and_register_res::<SimulationProgress>()
+            .init_and_register_res::<SimulationStats>()
+            .init_and_register_res::<VisualizationSettings>()
+            .init_and_register_res::<CellInspector>()
+            .init_and_register_res::<FrameRecording>();
+
+        app.init_resource::<GameWorldUploads>()
+            .init_resource::<LoadedScenario>()
+            .init_resource::<InspectedRegion>()
+            .init_resource::<PipelineErrors>()
+            .init_resource::<SnapshotRequests>()
+            .init_resource::<FrameRequests>()
+            .insert_resource(SnapshotReceiver(snapshot_receiver))
+            .insert_resource(FrameReceiver(frame_receiver))
+            .insert_resource(PipelineErrorsReceiver(pipeline_errors_receiver))
+            .insert_resource(ProgressReceiver(progress_receiver))
+            .insert_resource(StatsReceiver(stats_receiver))
+            .insert_resource(InspectorReceiver(inspector_receiver));
 
         // Extract world resource from the main world into the render world
         // for operation on by the compute shader and display on the sprite.
-        app.add_plugins(ExtractResourcePlugin::<GameWorldData>::default());
+        app.add_plugins(ExtractResourcePlugin::<GameWorldData>::default())
+            .add_plugins(ExtractResourcePlugin::<SimulationParams>::default())
+            .add_plugins(ExtractResourcePlugin::<SimulationControl>::default())
+            .add_plugins(ExtractResourcePlugin::<SimulationClock>::default())
+            .add_plugins(ExtractResourcePlugin::<VisualizationSettings>::default())
+            .add_plugins(ExtractResourcePlugin::<CellInspector>::default())
+            .add_plugins(ExtractResourcePlugin::<FrameRecording>::default());
         let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .insert_resource(world_size)
+            .insert_resource(self.boundary)
+            .insert_resource(self.gravity)
+            .insert_resource(self.collisions)
+            .insert_resource(SnapshotSender(snapshot_sender))
+            .insert_resource(FrameSender(frame_sender))
+            .insert_resource(PipelineErrorsSender(pipeline_errors_sender))
+            .insert_resource(ProgressSender(progress_sender))
+            .insert_resource(StatsSender(stats_sender))
+            .insert_resource(InspectorSender(inspector_sender))
+            .init_resource::<SimulationProgress>()
+            .init_resource::<FrameRecorder>()
+            .init_resource::<FrameReadbacks>();
+        render_app.add_systems(ExtractSchedule, extract_transfers_sys);
         render_app.add_systems(
             Render,
-            prepare_bind_group_sys.in_set(RenderSet::PrepareBindGroups),
+            (
+                prepare_bind_group_sys,
+                prepare_frames_sys,
+                prepare_transfers_sys,
+            )
+                .chain()
+                .in_set(RenderSet::PrepareBindGroups),
+        );
+        render_app.add_systems(
+            Render,
+            (
+                send_progress_sys,
+                read_stats_sys,
+                read_inspector_sys,
+                read_frames_sys,
+                check_pipelines_sys,
+            )
+                .in_set(RenderSet::Cleanup),
         );
 
         let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
@@ -49,6 +199,52 @@ impl Plugin for GameWorldPlugin {
 
     fn finish(&self, app: &mut App) {
         let render_app = app.sub_app_mut(RenderApp);
-        render_app.init_resource::<GameWorldPipeline>();
+        render_app
+            .init_resource::<GameWorldPipeline>()
+            .init_resource::<PyramidBindGroups>()
+            .init_resource::<FftBindGroups>()
+            .init_resource::<StatsReadbacks>()
+            .init_resource::<InspectorReadback>();
+    }
+}
+
+/// Camera and sprite displaying the world with keyboard and mouse controls,
+/// requires [`GameWorldPlugin`].
+#[derive(Clone, Debug, Default)]
+pub struct GameWorldViewPlugin;
+
+impl Plugin for GameWorldViewPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.add_systems(PostStartup, world_view_init_sys);
+        app.add_systems(
+            Update,
+            (world_control_sys, world_paint_sys, inspect_cell_sys).chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                cell_inspector_ui_sys.after(inspect_cell_sys),
+                pipeline_errors_ui_sys,
+            ),
+        );
+        app.add_systems(
+            Update,
+            (
+                simulation_control_sys.before(reinitialize_world_sys),
+                world_snapshot_control_sys.before(save_world_snapshot_sys),
+                frame_control_sys.before(save_world_frame_sys),
+                visualization_control_sys,
+            ),
+        );
+
+        app.register_type::<WorldSprite>();
+
+        app.init_and_register_res::<GameWorldViewportScale>()
+            .init_and_register_res::<GameWorldSensitivity>()
+            .init_and_register_res::<BrushSettings>();
     }
 }
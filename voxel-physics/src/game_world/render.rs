@@ -1,108 +1,104 @@
 use bevy::{
     prelude::*,
-    render::{render_graph, render_resource::*, renderer::RenderContext},
+    render::{
+        render_graph,
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+    },
 };
 
-use crate::utils::pipeline_state::PipelineStateUtils;
+use std::mem::size_of;
 
-use super::{GameWorldBindGroup, GameWorldPipeline, WORKGROUP_SIZE, WORLD_SIZE};
-
-enum GameWorldState {
-    Loading,
-    Init,
-    UpdateGravity,
-    UpdateImpulse,
-    UpdatePosition,
-}
+use super::{
+    fft_passes, pyramid_level_sizes,
+    scheduler::{FrameInput, FramePlan, Phase, PhaseScheduler},
+    BoundaryMode, CellData, CellInspector, FftBindGroups, FftStep, FrameRecorder, FrameRecording,
+    GameWorldBindGroups, GameWorldData, GameWorldPipeline, GameWorldSize, InspectedRegion,
+    InspectorReadback, PyramidBindGroups, SimulationClock, SimulationControl, SimulationProgress,
+    StatsReadback, StatsReadbacks, WORKGROUP_SIZE,
+};
 
+#[derive(Default)]
 pub struct GameWorldNode {
-    state: GameWorldState,
-}
-
-impl GameWorldNode {
-    pub fn pre_update_required(&self) -> bool {
-        !matches!(self.state, GameWorldState::Init)
-            || !matches!(self.state, GameWorldState::Loading)
-    }
-
-    pub fn get_current_pipeline(
-        &self,
-        pipeline: &GameWorldPipeline,
-    ) -> Option<CachedComputePipelineId> {
-        match self.state {
-            GameWorldState::Loading => None,
-            GameWorldState::Init => Some(pipeline.init_pipeline),
-            GameWorldState::UpdateGravity => Some(pipeline.update_gravity_pipeline),
-            GameWorldState::UpdateImpulse => Some(pipeline.update_impulse_pipeline),
-            GameWorldState::UpdatePosition => Some(pipeline.update_position_pipeline),
-        }
-    }
-}
-
-impl Default for GameWorldNode {
-    fn default() -> Self {
-        Self {
-            state: GameWorldState::Loading,
-        }
-    }
+    scheduler: PhaseScheduler,
+    /// Phases dispatched during the current frame.
+    plan: FramePlan,
+    /// Index of the readback in [`StatsReadbacks`] the stats of every step
+    /// are reduced and copied into, or of the latest state if no step is
+    /// dispatched.
+    stats_readback: Option<usize>,
+    /// Whether cells around the inspected one are copied into
+    /// [`InspectorReadback`] after the phases.
+    inspect: bool,
 }
 
 impl render_graph::Node for GameWorldNode {
     fn update(&mut self, world: &mut World) {
         let pipeline = world.resource::<GameWorldPipeline>();
         let pipeline_cache = world.resource::<PipelineCache>();
+        let progress = world.resource::<SimulationProgress>();
+        let control = world.resource::<SimulationControl>();
+        let clock = world.resource::<SimulationClock>();
+        let recorder = world.resource::<FrameRecorder>();
+        let recording = world.resource::<FrameRecording>();
 
-        let is_pre_update_ready = pipeline_cache
-            .get_compute_pipeline_state(pipeline.pre_update_pipeline)
-            .is_ok();
+        // the frame ends at the next recorded step, and waits for it to be
+        // recorded if all frame readbacks are busy
+        let mut steps = control.steps_this_frame(progress.step, clock.steps);
+        if let Some(limit) = recorder.steps_to_next_frame(recording, progress.step) {
+            steps = steps.min(limit.try_into().unwrap_or(u32::MAX));
+        }
 
-        // if the corresponding pipeline has loaded, transition to the next stage
-        match self.state {
-            GameWorldState::Loading => {
-                if pipeline_cache
-                    .get_compute_pipeline_state(pipeline.init_pipeline)
-                    .is_ok()
-                {
-                    self.state = GameWorldState::Init;
-                }
-            }
-            GameWorldState::Init => {
-                if pipeline_cache
-                    .get_compute_pipeline_state(pipeline.update_gravity_pipeline)
-                    .is_ok()
-                    && is_pre_update_ready
-                {
-                    self.state = GameWorldState::UpdateGravity;
-                }
-            }
-            GameWorldState::UpdateGravity => {
-                if pipeline_cache
-                    .get_compute_pipeline_state(pipeline.update_impulse_pipeline)
-                    .is_ok()
-                    && is_pre_update_ready
-                {
-                    self.state = GameWorldState::UpdateImpulse;
-                }
-            }
-            GameWorldState::UpdateImpulse => {
-                if pipeline_cache
-                    .get_compute_pipeline_state(pipeline.update_position_pipeline)
-                    .is_ok()
-                    && is_pre_update_ready
-                {
-                    self.state = GameWorldState::UpdatePosition;
-                }
-            }
-            GameWorldState::UpdatePosition => {
-                if pipeline_cache
-                    .get_compute_pipeline_state(pipeline.update_gravity_pipeline)
-                    .is_ok()
-                    && is_pre_update_ready
-                {
-                    self.state = GameWorldState::UpdateGravity;
-                }
+        self.plan = self.scheduler.plan_frame(FrameInput {
+            pipelines_ready: pipeline.is_ready(pipeline_cache),
+            initialized: progress.initialized,
+            steps,
+        });
+        let output = self.plan.output;
+
+        let mut progress = world.resource_mut::<SimulationProgress>();
+        progress.initialized = self.plan.initialized;
+        progress.step += self.plan.steps as u64;
+        let step = progress.step;
+
+        let world_size = *world.resource::<GameWorldSize>();
+        self.stats_readback = None;
+        if output {
+            let steps: Vec<_> = if self.plan.steps > 0 {
+                (step + 1 - self.plan.steps as u64..=step).collect()
+            } else {
+                vec![step]
+            };
+            let render_device = world.resource::<RenderDevice>().clone();
+            let mut readbacks = world.resource_mut::<StatsReadbacks>();
+            self.stats_readback = readbacks.reserve(&render_device, world_size, steps.clone());
+            if self.stats_readback.is_none() {
+                warn!(
+                    "stats of steps {}..={} are dropped, all stats readbacks are busy",
+                    steps[0], step
+                );
             }
         }
+
+        let inspected = world.resource::<CellInspector>().location;
+        let boundary = *world.resource::<BoundaryMode>();
+        let mut readback = world.resource_mut::<InspectorReadback>();
+        self.inspect = false;
+        if let Some(center) = inspected.filter(|_| output && readback.readback.is_free()) {
+            readback.readback.reserve();
+            readback.region = InspectedRegion {
+                center,
+                step,
+                cells: Vec::new(),
+            };
+            readback.cell_indices = InspectedRegion::cell_indices(center, world_size, boundary);
+            self.inspect = true;
+        }
+
+        // the latest state must become `data_prev` for the next frame
+        if self.plan.swaps_buffers() {
+            world.resource_mut::<GameWorldData>().swap();
+        }
     }
 
     fn run(
@@ -111,43 +107,170 @@ impl render_graph::Node for GameWorldNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
-        let world_bind_group = world.resource::<GameWorldBindGroup>();
+        if !self.plan.output {
+            return Ok(());
+        }
+
+        let world_bind_groups = world.resource::<GameWorldBindGroups>();
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<GameWorldPipeline>();
+        let world_size = *world.resource::<GameWorldSize>();
+        let (workgroups_x, workgroups_y) = world_size.workgroups();
+        let pyramid_bind_groups = world.resource::<PyramidBindGroups>();
+        let pyramid_levels = pyramid_level_sizes(world_size);
+        let fft_bind_groups = world.resource::<FftBindGroups>();
+        let fft_passes = fft_passes(world_size);
+        let game_world_data = world.resource::<GameWorldData>();
+        let stats_readback = self
+            .stats_readback
+            .map(|index| &world.resource::<StatsReadbacks>().0[index]);
+        let reduce_stats = pipeline_cache
+            .get_compute_pipeline(pipeline.reduce_stats_pipeline)
+            .unwrap();
+        // slot of the next step in the stats readback
+        let mut stats_slot = 0;
+
+        let mut pass = begin_world_pass(render_context);
+
+        for (index, phase) in self.plan.phases.iter().enumerate() {
+            // phases ping-pong between the buffers
+            pass.set_bind_group(0, &world_bind_groups[index % 2], &[]);
+
+            // the mass pyramid is built from the same `data_prev`, level by
+            // level, there are no pyramid bind groups with relay gravity
+            if *phase == Phase::UpdateGravity && !pyramid_bind_groups.is_empty() {
+                let build_pyramid = pipeline_cache
+                    .get_compute_pipeline(pipeline.build_pyramid_pipeline)
+                    .unwrap();
+                pass.set_pipeline(build_pyramid);
+                for (bind_group, (width, height)) in pyramid_bind_groups.iter().zip(&pyramid_levels)
+                {
+                    pass.set_bind_group(1, bind_group, &[]);
+                    pass.dispatch_workgroups(
+                        width.div_ceil(WORKGROUP_SIZE),
+                        height.div_ceil(WORKGROUP_SIZE),
+                        1,
+                    );
+                }
+            }
+
+            // same for the FFT passes, there are no FFT bind groups without
+            // FFT gravity
+            if *phase == Phase::UpdateGravity && !fft_bind_groups.is_empty() {
+                for (bind_group, (step, params)) in fft_bind_groups.iter().zip(&fft_passes) {
+                    let fft_pipeline = match step {
+                        FftStep::Density => pipeline.fft_density_pipeline,
+                        FftStep::Butterfly => pipeline.fft_butterfly_pipeline,
+                        FftStep::Convolve => pipeline.fft_convolve_pipeline,
+                    };
+                    pass.set_pipeline(pipeline_cache.get_compute_pipeline(fft_pipeline).unwrap());
+                    pass.set_bind_group(1, bind_group, &[]);
+
+                    let (workgroups_x, workgroups_y) = step.workgroups(params, world_size);
+                    pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+                }
+            }
+
+            let phase_pipeline = pipeline_cache
+                .get_compute_pipeline(phase_pipeline(pipeline, *phase))
+                .unwrap();
+            pass.set_pipeline(phase_pipeline);
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+
+            // stats of every step, the pass ends to copy them out before the
+            // next step overwrites them
+            if let Some(readback) = stats_readback.filter(|_| *phase == Phase::UpdatePosition) {
+                pass.set_bind_group(0, &world_bind_groups[(index + 1) % 2], &[]);
+                pass.set_pipeline(reduce_stats);
+                pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+                drop(pass);
+
+                copy_stats(render_context, game_world_data, readback, stats_slot);
+                stats_slot += 1;
 
-        let mut pass =
-            render_context
-                .command_encoder()
-                .begin_compute_pass(&ComputePassDescriptor {
-                    label: Some("GameWorld compute pass"),
-                });
-
-        pass.set_bind_group(0, world_bind_group, &[]);
-        if self.pre_update_required() {
-            let pipeline = pipeline_cache
-                .get_compute_pipeline(pipeline.pre_update_pipeline)
+                pass = begin_world_pass(render_context);
+            }
+        }
+
+        // the bind group reading the buffer the last phase wrote into
+        pass.set_bind_group(0, &world_bind_groups[self.plan.phases.len() % 2], &[]);
+
+        if self.plan.output {
+            let draw = pipeline_cache
+                .get_compute_pipeline(pipeline.draw_pipeline)
                 .unwrap();
-            pass.set_pipeline(pipeline);
-            pass.dispatch_workgroups(
-                WORLD_SIZE.0 / WORKGROUP_SIZE,
-                WORLD_SIZE.1 / WORKGROUP_SIZE,
-                1,
-            );
+            pass.set_pipeline(draw);
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
 
-        // select the pipeline based on the current state
-        let pipeline = self.get_current_pipeline(pipeline);
-
-        if let Some(pipeline) = pipeline {
-            let pipeline = pipeline_cache.get_compute_pipeline(pipeline).unwrap();
-            pass.set_pipeline(pipeline);
-            pass.dispatch_workgroups(
-                WORLD_SIZE.0 / WORKGROUP_SIZE,
-                WORLD_SIZE.1 / WORKGROUP_SIZE,
-                1,
-            );
+        // stats of the latest state if no step is dispatched
+        let latest_stats = stats_readback.filter(|_| stats_slot == 0);
+        if latest_stats.is_some() {
+            pass.set_pipeline(reduce_stats);
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        }
+
+        drop(pass);
+
+        if let Some(readback) = latest_stats {
+            copy_stats(render_context, game_world_data, readback, 0);
+        }
+
+        // `update` already swapped the buffers, so `data_prev` is the latest
+        // state
+        if self.inspect {
+            let readback = world.resource::<InspectorReadback>();
+            let stride = size_of::<CellData>() as u64;
+            for (slot, index) in readback.cell_indices.iter().enumerate() {
+                let Some(index) = index else {
+                    continue;
+                };
+                render_context.command_encoder().copy_buffer_to_buffer(
+                    &game_world_data.data_prev,
+                    *index as u64 * stride,
+                    &readback.readback.staging,
+                    slot as u64 * stride,
+                    stride,
+                );
+            }
         }
 
         Ok(())
     }
 }
+
+fn begin_world_pass(render_context: &mut RenderContext) -> ComputePass<'_> {
+    render_context
+        .command_encoder()
+        .begin_compute_pass(&ComputePassDescriptor {
+            label: Some("GameWorld compute pass"),
+        })
+}
+
+/// Copies the partial sums reduced by `reduce_stats` into the slot of the
+/// stats readback.
+fn copy_stats(
+    render_context: &mut RenderContext,
+    game_world_data: &GameWorldData,
+    readback: &StatsReadback,
+    slot: u64,
+) {
+    let size = game_world_data.stats.size();
+    render_context.command_encoder().copy_buffer_to_buffer(
+        &game_world_data.stats,
+        0,
+        &readback.readback.staging,
+        slot * size,
+        size,
+    );
+}
+
+fn phase_pipeline(pipeline: &GameWorldPipeline, phase: Phase) -> CachedComputePipelineId {
+    match phase {
+        Phase::Init => pipeline.init_pipeline,
+        Phase::PreUpdate => pipeline.pre_update_pipeline,
+        Phase::UpdateGravity => pipeline.update_gravity_pipeline,
+        Phase::UpdateImpulse => pipeline.update_impulse_pipeline,
+        Phase::UpdatePosition => pipeline.update_position_pipeline,
+    }
+}
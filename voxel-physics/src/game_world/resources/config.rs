@@ -0,0 +1,234 @@
+use std::{fmt, str::FromStr};
+
+use bevy::{prelude::*, render::render_resource::ShaderDefVal};
+
+use crate::game_world::{DEFAULT_WORLD_SIZE, WORKGROUP_SIZE};
+
+/// Size of the world in cells.
+///
+/// Set once by [`GameWorldPlugin`](crate::game_world::GameWorldPlugin), all
+/// buffers, the bind group layout, dispatch counts and shader constants are
+/// derived from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct GameWorldSize(pub (u32, u32));
+
+impl Default for GameWorldSize {
+    fn default() -> Self {
+        Self(DEFAULT_WORLD_SIZE)
+    }
+}
+
+impl GameWorldSize {
+    pub fn width(&self) -> u32 {
+        self.0 .0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.0 .1
+    }
+
+    pub fn cells_count(&self) -> usize {
+        (self.width() * self.height()) as usize
+    }
+
+    /// Index of the cell in the storage buffers, same as `location_to_index`
+    /// in `world_data.wgsl` for locations within bounds.
+    pub fn cell_index(&self, location: UVec2) -> usize {
+        (location.y * self.width() + location.x) as usize
+    }
+
+    /// Number of workgroups required to cover the whole world.
+    pub fn workgroups(&self) -> (u32, u32) {
+        (
+            self.width().div_ceil(WORKGROUP_SIZE),
+            self.height().div_ceil(WORKGROUP_SIZE),
+        )
+    }
+
+    /// Shader defs for `WORLD_WIDTH` and `WORLD_HEIGHT` in `constants.wgsl`
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        vec![
+            ShaderDefVal::Int("WORLD_WIDTH".into(), self.width() as i32),
+            ShaderDefVal::Int("WORLD_HEIGHT".into(), self.height() as i32),
+        ]
+    }
+}
+
+/// What happens to particles and gravity at the edges of the world.
+///
+/// Set once by [`GameWorldPlugin`](crate::game_world::GameWorldPlugin) and
+/// compiled into the shaders. Keep in sync with `BOUNDARY_*` in
+/// `constants.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+#[repr(u32)]
+pub enum BoundaryMode {
+    /// The world wraps around the edges
+    #[default]
+    Periodic = 0,
+    /// Particles bounce off the edges with inverted impulse, gravity doesn't
+    /// cross them
+    Reflect = 1,
+    /// Particles crossing the edges are deleted and their mass is reported in
+    /// [`SimulationStats::lost_mass`](crate::game_world::SimulationStats::lost_mass),
+    /// gravity doesn't cross them
+    Absorb = 2,
+}
+
+impl BoundaryMode {
+    /// Shader def for `BOUNDARY_MODE` in `constants.wgsl`
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        vec![ShaderDefVal::UInt("BOUNDARY_MODE".into(), *self as u32)]
+    }
+}
+
+impl fmt::Display for BoundaryMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Periodic => write!(f, "periodic"),
+            Self::Reflect => write!(f, "reflect"),
+            Self::Absorb => write!(f, "absorb"),
+        }
+    }
+}
+
+impl FromStr for BoundaryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "periodic" => Ok(Self::Periodic),
+            "reflect" => Ok(Self::Reflect),
+            "absorb" => Ok(Self::Absorb),
+            _ => Err(format!(
+                "unknown boundary mode {s:?}, expected periodic, reflect or absorb"
+            )),
+        }
+    }
+}
+
+/// How `update_gravity` computes the field of the particles.
+///
+/// Set once by [`GameWorldPlugin`](crate::game_world::GameWorldPlugin) and
+/// compiled into the shaders. Keep in sync with `GRAVITY_*` in
+/// `constants.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+#[repr(u32)]
+pub enum GravityMode {
+    /// Fields are relayed between neighbor cells and propagate one cell per
+    /// step, see `gravity_data.wgsl`
+    #[default]
+    Relay = 0,
+    /// Field of all particles is evaluated every step from a pyramid of
+    /// downsampled masses, see `pyramid.wgsl`
+    Pyramid = 1,
+    /// Field of all particles is solved from the mass density with FFT every
+    /// step and falls off as `1 / distance`, see `fft.wgsl`. Requires a
+    /// periodic world with power of two sizes, see [`GravityMode::check`]
+    Fft = 2,
+}
+
+impl GravityMode {
+    /// Shader def for `GRAVITY_MODE` in `constants.wgsl`
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        vec![ShaderDefVal::UInt("GRAVITY_MODE".into(), *self as u32)]
+    }
+
+    /// Checks whether the mode supports the world.
+    pub fn check(&self, world_size: GameWorldSize, boundary: BoundaryMode) -> Result<(), String> {
+        if *self != Self::Fft {
+            return Ok(());
+        }
+
+        if !world_size.width().is_power_of_two() || !world_size.height().is_power_of_two() {
+            return Err(format!(
+                "{self} gravity requires power of two world sizes, got {}x{}",
+                world_size.width(),
+                world_size.height()
+            ));
+        }
+
+        if boundary != BoundaryMode::Periodic {
+            return Err(format!(
+                "{self} gravity requires periodic boundary, got {boundary}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for GravityMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Relay => write!(f, "relay"),
+            Self::Pyramid => write!(f, "pyramid"),
+            Self::Fft => write!(f, "fft"),
+        }
+    }
+}
+
+impl FromStr for GravityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relay" => Ok(Self::Relay),
+            "pyramid" => Ok(Self::Pyramid),
+            "fft" => Ok(Self::Fft),
+            _ => Err(format!(
+                "unknown gravity mode {s:?}, expected relay, pyramid or fft"
+            )),
+        }
+    }
+}
+
+/// What happens to particles moving to the same cell.
+///
+/// Set once by [`GameWorldPlugin`](crate::game_world::GameWorldPlugin) and
+/// compiled into the shaders, how particles bounce is set by
+/// [`SimulationParams`](crate::game_world::SimulationParams). Keep in sync
+/// with `COLLISION_*` in `constants.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+#[repr(u32)]
+pub enum CollisionMode {
+    /// Colliding particles always merge
+    #[default]
+    Merge = 0,
+    /// Colliding particles bounce off each other unless they are slow, see
+    /// `collision.wgsl`
+    Bounce = 1,
+}
+
+impl CollisionMode {
+    /// Shader def for `COLLISION_MODE` in `constants.wgsl`
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        vec![ShaderDefVal::UInt("COLLISION_MODE".into(), *self as u32)]
+    }
+}
+
+impl fmt::Display for CollisionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Merge => write!(f, "merge"),
+            Self::Bounce => write!(f, "bounce"),
+        }
+    }
+}
+
+impl FromStr for CollisionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(Self::Merge),
+            "bounce" => Ok(Self::Bounce),
+            _ => Err(format!(
+                "unknown collision mode {s:?}, expected merge or bounce"
+            )),
+        }
+    }
+}
@@ -1,6 +1,11 @@
-use bevy::prelude::*;
+use std::time::Duration;
 
-use crate::game_world::{DEFAULT_SCALE, DEFAULT_SENSITIVITY};
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
+
+use crate::game_world::{
+    DEFAULT_BRUSH_MASS, DEFAULT_BRUSH_RADIUS, DEFAULT_SCALE, DEFAULT_SENSITIVITY,
+    DEFAULT_STEPS_PER_FRAME, DEFAULT_TICK_RATE, MAX_STEPS_PER_FRAME,
+};
 
 #[derive(Clone, Copy, Debug, Resource, Reflect)]
 #[reflect(Resource)]
@@ -21,3 +26,212 @@ impl Default for GameWorldSensitivity {
         Self(DEFAULT_SENSITIVITY)
     }
 }
+
+/// Brush for painting particles with the mouse.
+#[derive(Clone, Copy, Debug, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct BrushSettings {
+    /// Radius of the brush in cells
+    pub radius: f32,
+    /// Mass of every painted particle
+    pub mass: f32,
+    /// Initial velocity of painted particles in cells per step
+    pub velocity: Vec2,
+}
+
+impl Default for BrushSettings {
+    fn default() -> Self {
+        Self {
+            radius: DEFAULT_BRUSH_RADIUS,
+            mass: DEFAULT_BRUSH_MASS,
+            velocity: Vec2::ZERO,
+        }
+    }
+}
+
+/// Controls how many simulation steps are dispatched every frame.
+#[derive(Clone, Copy, Debug, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub struct SimulationControl {
+    pub paused: bool,
+    /// Dispatches a single step during the next frame while paused, reset
+    /// automatically.
+    pub single_step: bool,
+    /// Full simulation steps per second independent of the frame rate,
+    /// `steps_per_frame` steps are dispatched every frame if `None`.
+    pub tick_rate: Option<f32>,
+    /// Number of full simulation steps dispatched every frame while not
+    /// paused and without `tick_rate`.
+    pub steps_per_frame: u32,
+    /// Max number of full simulation steps dispatched in a single frame with
+    /// `tick_rate`. Steps above it are dropped, so a slow frame doesn't make
+    /// the next one even slower.
+    pub max_steps_per_frame: u32,
+    /// The simulation doesn't advance past this step.
+    pub stop_at_step: Option<u64>,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            single_step: false,
+            tick_rate: Some(DEFAULT_TICK_RATE),
+            steps_per_frame: DEFAULT_STEPS_PER_FRAME,
+            max_steps_per_frame: MAX_STEPS_PER_FRAME,
+            stop_at_step: None,
+        }
+    }
+}
+
+impl SimulationControl {
+    /// Number of full simulation steps to dispatch during the current frame
+    /// when `step` steps are already done and `scheduled` steps are due
+    /// according to [`SimulationClock`].
+    pub fn steps_this_frame(&self, step: u64, scheduled: u32) -> u32 {
+        let steps = if !self.paused {
+            scheduled
+        } else if self.single_step {
+            1
+        } else {
+            0
+        };
+
+        match self.stop_at_step {
+            Some(stop_at_step) => {
+                let remaining = stop_at_step.saturating_sub(step);
+                steps.min(remaining.try_into().unwrap_or(u32::MAX))
+            }
+            None => steps,
+        }
+    }
+}
+
+/// Schedules simulation steps at [`SimulationControl::tick_rate`].
+///
+/// Ticked in the main world at the end of every frame, the render world
+/// dispatches `steps` during the same frame.
+#[derive(Clone, Copy, Debug, Default, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub struct SimulationClock {
+    /// Steps due but not scheduled yet, less than one unless steps were
+    /// dropped by the cap.
+    pub accumulator: f64,
+    /// Steps scheduled for the current frame while not paused.
+    pub steps: u32,
+}
+
+impl SimulationClock {
+    /// Schedules steps for a frame which took `delta`.
+    pub fn tick(&mut self, control: &SimulationControl, delta: Duration) {
+        let Some(tick_rate) = control.tick_rate.filter(|_| !control.paused) else {
+            self.accumulator = 0.0;
+            self.steps = if control.paused {
+                0
+            } else {
+                control.steps_per_frame
+            };
+            return;
+        };
+
+        self.accumulator += delta.as_secs_f64() * tick_rate as f64;
+        let due = self.accumulator.floor();
+
+        if due > control.max_steps_per_frame as f64 {
+            // falling behind, drop the backlog instead of trying to catch up
+            self.steps = control.max_steps_per_frame;
+            self.accumulator = 0.0;
+        } else {
+            self.steps = due as u32;
+            self.accumulator -= due;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_clock(control: &SimulationControl, frame_rate: u32, seconds: u32) -> u64 {
+        let mut clock = SimulationClock::default();
+        let delta = Duration::from_secs(1) / frame_rate;
+
+        let mut steps = 0;
+        for _ in 0..frame_rate * seconds {
+            clock.tick(control, delta);
+            steps += control.steps_this_frame(steps, clock.steps) as u64;
+        }
+        steps
+    }
+
+    #[test]
+    fn tick_rate_is_independent_of_frame_rate() {
+        let control = SimulationControl {
+            tick_rate: Some(240.0),
+            ..default()
+        };
+
+        for frame_rate in [30, 60, 75, 144, 240, 360] {
+            let steps = run_clock(&control, frame_rate, 10);
+            assert!(steps.abs_diff(2400) <= 1, "{frame_rate} fps: {steps} steps");
+        }
+    }
+
+    #[test]
+    fn steps_above_cap_are_dropped() {
+        let control = SimulationControl {
+            tick_rate: Some(240.0),
+            max_steps_per_frame: 8,
+            ..default()
+        };
+        let mut clock = SimulationClock::default();
+
+        clock.tick(&control, Duration::from_secs(1));
+        assert_eq!(clock.steps, 8);
+
+        // the backlog isn't carried over to the next frame
+        clock.tick(&control, Duration::from_secs_f64(1.0 / 240.0));
+        assert_eq!(clock.steps, 1);
+    }
+
+    #[test]
+    fn steps_per_frame_without_tick_rate() {
+        let control = SimulationControl {
+            tick_rate: None,
+            steps_per_frame: 3,
+            ..default()
+        };
+
+        assert_eq!(run_clock(&control, 60, 1), 180);
+    }
+
+    #[test]
+    fn paused_clock_schedules_nothing() {
+        let mut control = SimulationControl {
+            paused: true,
+            ..default()
+        };
+        let mut clock = SimulationClock::default();
+
+        clock.tick(&control, Duration::from_secs(1));
+        assert_eq!(clock.steps, 0);
+        assert_eq!(clock.accumulator, 0.0);
+
+        control.single_step = true;
+        assert_eq!(control.steps_this_frame(0, clock.steps), 1);
+    }
+
+    #[test]
+    fn steps_stop_at_step() {
+        let control = SimulationControl {
+            tick_rate: None,
+            steps_per_frame: 16,
+            stop_at_step: Some(20),
+            ..default()
+        };
+
+        assert_eq!(control.steps_this_frame(0, 16), 16);
+        assert_eq!(control.steps_this_frame(16, 16), 4);
+        assert_eq!(control.steps_this_frame(20, 16), 0);
+    }
+}
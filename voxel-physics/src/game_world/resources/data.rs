@@ -6,18 +6,58 @@ use bevy::{
 };
 use bytemuck::{Pod, Zeroable};
 
-#[derive(Default, Clone, Copy, Pod, Zeroable)]
+use crate::game_world::{
+    GameWorldSize, SimulationParams, VisualizationParams, CELL_CENTER, PARTICLE_NOTHING,
+    PARTICLE_REGULAR,
+};
+
+/// State of a single cell, stored in the storage buffers as is.
+///
+/// Keep in sync with `CellData` in `world_data.wgsl`, the layout is checked
+/// against the shader by tests.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
 #[repr(C)]
 pub struct CellData {
+    /// Position of gravity source relative to current cell, see
+    /// `gravity_data.wgsl`
     pub to_gravity_source: Vec2,
+    /// Mass of gravity source
     pub gravity_strength: f32,
     pub particle_type: u32,
     pub mass: f32,
+    /// WGSL aligns `impulse` to the 8 bytes of `vec2`
+    pub _padding: u32,
     pub impulse: Vec2,
     pub relative_pos: Vec2,
 }
 
 impl CellData {
+    /// Same as `new_empty_cell` in `world_data.wgsl`
+    pub fn new_empty() -> Self {
+        Self {
+            to_gravity_source: Vec2::ZERO,
+            gravity_strength: 0.0,
+            particle_type: PARTICLE_NOTHING,
+            mass: 0.0,
+            _padding: 0,
+            impulse: Vec2::ZERO,
+            relative_pos: CELL_CENTER,
+        }
+    }
+
+    /// Same as `new_particle_cell` in `world_data.wgsl`
+    pub fn new_particle(mass: f32, particle_vel: Vec2) -> Self {
+        Self {
+            to_gravity_source: Vec2::ZERO,
+            gravity_strength: 0.0,
+            particle_type: PARTICLE_REGULAR,
+            mass,
+            _padding: 0,
+            impulse: particle_vel * mass,
+            relative_pos: CELL_CENTER,
+        }
+    }
+
     pub fn get_world_data_size(world_size: (u32, u32)) -> u64 {
         ((world_size.0 * world_size.1) as usize * size_of::<Self>()) as u64
     }
@@ -33,6 +73,44 @@ pub struct GameWorldData {
     /// The next state of the world. (Array of [`CellData`])
     #[storage(2, visibility(compute), buffer)]
     pub data_next: Buffer,
+    /// Partial sums written by `reduce_stats` pass. (Array of
+    /// [`StatsPartial`](crate::game_world::StatsPartial))
+    #[storage(4, visibility(compute), buffer)]
+    pub stats: Buffer,
+    /// Mass absorbed by the edges per cell, see
+    /// [`BoundaryMode::Absorb`](crate::game_world::BoundaryMode::Absorb).
+    /// (Array of `f32`)
+    #[storage(5, visibility(compute), buffer)]
+    pub lost_mass: Buffer,
+    /// Mass pyramid rebuilt before every `update_gravity` pass with
+    /// [`GravityMode::Pyramid`](crate::game_world::GravityMode::Pyramid).
+    /// (Array of [`PyramidNode`](crate::game_world::PyramidNode))
+    #[storage(7, visibility(compute), buffer)]
+    pub pyramid: Buffer,
+    /// Values transformed by the passes of
+    /// [`GravityMode::Fft`](crate::game_world::GravityMode::Fft) before every
+    /// `update_gravity` pass. (Array of `Vec2`)
+    #[storage(8, visibility(compute), buffer)]
+    pub fft: Buffer,
+    /// Spectrum of the field of a unit mass, see
+    /// [`fft_kernel_spectrum`](crate::game_world::fft_kernel_spectrum).
+    /// (Array of `Vec2`)
+    #[storage(9, visibility(compute), buffer, read_only)]
+    pub fft_kernel: Buffer,
+    /// Copied from [`SimulationParams`] in the render world right before the
+    /// bind group is prepared.
+    #[uniform(3)]
+    pub params: SimulationParams,
+    /// Copied from
+    /// [`VisualizationSettings`](crate::game_world::VisualizationSettings) in
+    /// the render world right before the bind group is prepared.
+    #[uniform(6)]
+    pub visualization: VisualizationParams,
+}
+
+/// Size of [`GameWorldData::lost_mass`] buffer.
+pub fn get_lost_mass_data_size(world_size: GameWorldSize) -> u64 {
+    (world_size.cells_count() * size_of::<f32>()) as u64
 }
 
 impl GameWorldData {
@@ -42,11 +120,48 @@ impl GameWorldData {
     }
 }
 
+/// Bind groups for both directions of the ping-pong between the buffers: the
+/// first one reads `data_prev` and writes `data_next`, the second one is
+/// swapped.
 #[derive(Clone, Debug, Resource, ExtractResource, Deref, DerefMut)]
-pub struct GameWorldBindGroup(pub BindGroup);
+pub struct GameWorldBindGroups(pub [BindGroup; 2]);
+
+impl From<[BindGroup; 2]> for GameWorldBindGroups {
+    fn from(bind_groups: [BindGroup; 2]) -> Self {
+        Self(bind_groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::{
+        game_world::GameWorldPipeline,
+        utils::shaders::{validate_shader, StructLayout},
+    };
+
+    #[test]
+    fn cell_layout_matches_shader() {
+        let shader_defs =
+            GameWorldPipeline::shader_defs(default(), default(), default(), default());
+        let module = validate_shader("shaders/world_data.wgsl", shader_defs);
+        let layout = StructLayout::of(&module, "CellData");
 
-impl From<BindGroup> for GameWorldBindGroup {
-    fn from(bind_group: BindGroup) -> Self {
-        Self(bind_group)
+        assert_eq!(layout.size, size_of::<CellData>());
+        // `_padding` is implicit in WGSL
+        assert_eq!(
+            layout.members,
+            [
+                ("to_gravity_source", offset_of!(CellData, to_gravity_source)),
+                ("gravity_strength", offset_of!(CellData, gravity_strength)),
+                ("particle_type", offset_of!(CellData, particle_type)),
+                ("mass", offset_of!(CellData, mass)),
+                ("impulse", offset_of!(CellData, impulse)),
+                ("relative_pos", offset_of!(CellData, relative_pos)),
+            ]
+            .map(|(name, offset)| (name.to_string(), offset))
+        );
     }
 }
@@ -0,0 +1,370 @@
+use std::{f32::consts::PI, mem::size_of};
+
+use bevy::{
+    prelude::*,
+    render::{render_resource::*, renderer::RenderDevice},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::game_world::{GameWorldPipeline, GameWorldSize, GravityMode, WORKGROUP_SIZE};
+
+/// Uniform of a pass of FFT gravity, see `fft.wgsl`.
+///
+/// Keep in sync with `FftParams` in `fft.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct FftParams {
+    /// Half of `fft` read by the pass, the other half is written
+    pub source: u32,
+    /// Axis transformed by `fft_butterfly`, `0` for rows and `1` for columns
+    pub axis: u32,
+    /// Length of the transforms merged by `fft_butterfly`
+    pub span: u32,
+    /// Whether `fft_butterfly` runs the inverse transform
+    pub inverse: u32,
+}
+
+/// Entry point of a pass of FFT gravity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FftStep {
+    Density,
+    Butterfly,
+    Convolve,
+}
+
+impl FftStep {
+    /// Number of workgroups required to cover the pass, a butterfly handles
+    /// two elements of its row or column.
+    pub fn workgroups(&self, params: &FftParams, world_size: GameWorldSize) -> (u32, u32) {
+        let (width, height) = match self {
+            Self::Butterfly if params.axis == 0 => (world_size.width() / 2, world_size.height()),
+            Self::Butterfly => (world_size.width(), world_size.height() / 2),
+            Self::Density | Self::Convolve => return world_size.workgroups(),
+        };
+        (
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+        )
+    }
+}
+
+/// Passes of FFT gravity in the order they are dispatched before
+/// `update_gravity`: the density, the forward transform of the rows and then
+/// the columns, the convolution and the inverse transform.
+///
+/// Every pass reads the half of `fft` written by the previous one, there is
+/// an even number of passes, so the field ends up in the first half.
+pub fn fft_passes(world_size: GameWorldSize) -> Vec<(FftStep, FftParams)> {
+    let transform = |inverse: u32| {
+        [world_size.width(), world_size.height()]
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(axis, length)| {
+                (0..length.trailing_zeros()).map(move |stage| {
+                    let params = FftParams {
+                        axis: axis as u32,
+                        span: 1 << stage,
+                        inverse,
+                        ..default()
+                    };
+                    (FftStep::Butterfly, params)
+                })
+            })
+    };
+
+    let mut passes: Vec<_> = [(FftStep::Density, FftParams::default())]
+        .into_iter()
+        .chain(transform(0))
+        .chain([(FftStep::Convolve, FftParams::default())])
+        .chain(transform(1))
+        .collect();
+    for (index, (_, params)) in passes.iter_mut().enumerate() {
+        params.source = index as u32 % 2;
+    }
+
+    passes
+}
+
+/// Spectrum of the field of a unit mass at the origin, the solution of the
+/// Poisson equation `∇²φ = 2πρ` of the periodic world, so the field of a mass
+/// is `mass / distance` like in a plane.
+///
+/// The spectrum of the potential is the density divided by `-|k|²`, the mean
+/// density has no potential, so the `k = 0` mode is zero. The field is the
+/// central difference of the potential, `-i sin(k)` times its spectrum, packed
+/// as `x + iy`. Unlike the exact gradient `-ik`, it vanishes at the Nyquist
+/// frequency instead of flipping sign there, so the field is odd and doesn't
+/// alternate between even and odd cells, and particles don't feel themselves.
+pub fn fft_kernel_spectrum(world_size: GameWorldSize) -> Vec<Vec2> {
+    let (width, height) = world_size.0;
+
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| UVec2::new(x, y)))
+        .map(|frequency| {
+            let k = Vec2::new(
+                wavenumber(frequency.x, width),
+                wavenumber(frequency.y, height),
+            );
+            if k == Vec2::ZERO {
+                return Vec2::ZERO;
+            }
+
+            let gradient = Vec2::new(k.x.sin(), k.y.sin());
+            // 2π i (gradient.x + i gradient.y) / |k|²
+            gradient.perp() * 2.0 * PI / k.length_squared()
+        })
+        .collect()
+}
+
+/// Angular wavenumber of the frequency along an axis, frequencies above half
+/// of the axis are negative.
+fn wavenumber(frequency: u32, length: u32) -> f32 {
+    let frequency = if 2 * frequency > length {
+        frequency as i32 - length as i32
+    } else {
+        frequency as i32
+    };
+    2.0 * PI * frequency as f32 / length as f32
+}
+
+/// Same as `fft_index` in `fft.wgsl`
+pub fn fft_index(size: (u32, u32), half: u32, location: IVec2) -> usize {
+    (half * size.0 * size.1) as usize + (location.y * size.0 as i32 + location.x) as usize
+}
+
+/// Same as `complex_mul` in `fft.wgsl`
+pub fn complex_mul(a: Vec2, b: Vec2) -> Vec2 {
+    Vec2::new(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x)
+}
+
+/// Same as `axis_location` in `fft.wgsl`
+fn axis_location(axis: u32, position: i32, line: i32) -> IVec2 {
+    if axis == 0 {
+        return IVec2::new(position, line);
+    }
+
+    IVec2::new(line, position)
+}
+
+/// Same as `fft_butterfly` entry point, `fft` holds both halves.
+pub fn fft_butterfly(fft: &mut [Vec2], size: (u32, u32), params: &FftParams) {
+    let (length, lines) = if params.axis == 0 {
+        (size.0 as i32, size.1 as i32)
+    } else {
+        (size.1 as i32, size.0 as i32)
+    };
+    let half_length = length / 2;
+    let span = params.span as i32;
+
+    for line in 0..lines {
+        for pair in 0..half_length {
+            let k = pair % span;
+
+            let mut angle = -PI * k as f32 / span as f32;
+            if params.inverse != 0 {
+                angle = -angle;
+            }
+
+            let even = fft[fft_index(size, params.source, axis_location(params.axis, pair, line))];
+            let odd = fft[fft_index(
+                size,
+                params.source,
+                axis_location(params.axis, pair + half_length, line),
+            )];
+            let twiddled = complex_mul(odd, Vec2::new(angle.cos(), angle.sin()));
+
+            let output = (pair / span) * span * 2 + k;
+            fft[fft_index(
+                size,
+                1 - params.source,
+                axis_location(params.axis, output, line),
+            )] = even + twiddled;
+            fft[fft_index(
+                size,
+                1 - params.source,
+                axis_location(params.axis, output + span, line),
+            )] = even - twiddled;
+        }
+    }
+}
+
+/// Size of [`GameWorldData::fft`](crate::game_world::GameWorldData::fft)
+/// buffer, a single unused value without FFT gravity.
+pub fn get_fft_data_size(world_size: GameWorldSize, gravity: GravityMode) -> u64 {
+    let values = match gravity {
+        GravityMode::Fft => 2 * world_size.cells_count(),
+        GravityMode::Relay | GravityMode::Pyramid => 1,
+    };
+    (values * size_of::<Vec2>()) as u64
+}
+
+/// Size of
+/// [`GameWorldData::fft_kernel`](crate::game_world::GameWorldData::fft_kernel)
+/// buffer, a single unused value without FFT gravity.
+pub fn get_fft_kernel_data_size(world_size: GameWorldSize, gravity: GravityMode) -> u64 {
+    let values = match gravity {
+        GravityMode::Fft => world_size.cells_count(),
+        GravityMode::Relay | GravityMode::Pyramid => 1,
+    };
+    (values * size_of::<Vec2>()) as u64
+}
+
+/// Bind groups of [`FftParams`] for every pass of [`fft_passes`], empty
+/// without FFT gravity. Lives in the render world.
+#[derive(Clone, Debug, Resource, Deref)]
+pub struct FftBindGroups(pub Vec<BindGroup>);
+
+impl FromWorld for FftBindGroups {
+    fn from_world(world: &mut World) -> Self {
+        if *world.resource::<GravityMode>() != GravityMode::Fft {
+            return Self(Vec::new());
+        }
+
+        let passes = fft_passes(*world.resource::<GameWorldSize>());
+        let layout = &world.resource::<GameWorldPipeline>().fft_bind_group_layout;
+        let render_device = world.resource::<RenderDevice>();
+
+        let bind_groups = passes
+            .iter()
+            .map(|(_, params)| {
+                let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("fft_params"),
+                    contents: bytemuck::bytes_of(params),
+                    usage: BufferUsages::UNIFORM,
+                });
+                render_device.create_bind_group(
+                    Some("fft_bind_group"),
+                    layout,
+                    &BindGroupEntries::single(params.as_entire_binding()),
+                )
+            })
+            .collect();
+
+        Self(bind_groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::{
+        game_world::BoundaryMode,
+        utils::shaders::{validate_shader, StructLayout},
+    };
+
+    #[test]
+    fn passes_end_in_the_first_half() {
+        let passes = fft_passes(GameWorldSize((16, 4)));
+
+        // density, 4 + 2 forward, convolution, 4 + 2 inverse
+        assert_eq!(passes.len(), 14);
+        assert_eq!(passes[0].0, FftStep::Density);
+        assert_eq!(passes[7].0, FftStep::Convolve);
+        assert_eq!(
+            passes[1..7]
+                .iter()
+                .map(|(_, params)| (params.axis, params.span))
+                .collect::<Vec<_>>(),
+            [(0, 1), (0, 2), (0, 4), (0, 8), (1, 1), (1, 2)]
+        );
+        assert!(passes[8..].iter().all(|(_, params)| params.inverse == 1));
+        assert_eq!(passes.last().unwrap().1.source, 1);
+    }
+
+    #[test]
+    fn forward_passes_match_discrete_fourier_transform() {
+        let world_size = GameWorldSize((8, 4));
+        let (width, height) = world_size.0;
+        let cells = world_size.cells_count();
+        let values: Vec<_> = (0..cells)
+            .map(|index| Vec2::new((index * 7 % 5) as f32, (index * 3 % 4) as f32 - 1.5))
+            .collect();
+
+        let passes = fft_passes(world_size);
+        let (_, density) = passes[0];
+        let mut fft = vec![Vec2::ZERO; 2 * cells];
+        let mut half = 1 - density.source;
+        fft[half as usize * cells..][..cells].copy_from_slice(&values);
+        for (_, params) in passes[1..]
+            .iter()
+            .take_while(|(step, _)| *step == FftStep::Butterfly)
+        {
+            fft_butterfly(&mut fft, world_size.0, params);
+            half = 1 - params.source;
+        }
+
+        for frequency_y in 0..height {
+            for frequency_x in 0..width {
+                let mut expected = Vec2::ZERO;
+                for y in 0..height {
+                    for x in 0..width {
+                        let angle = -2.0
+                            * PI
+                            * ((frequency_x * x) as f32 / width as f32
+                                + (frequency_y * y) as f32 / height as f32);
+                        let value = values[(y * width + x) as usize];
+                        expected += complex_mul(value, Vec2::new(angle.cos(), angle.sin()));
+                    }
+                }
+
+                let frequency = IVec2::new(frequency_x as i32, frequency_y as i32);
+                let spectrum = fft[fft_index(world_size.0, half, frequency)];
+                assert!(
+                    (spectrum - expected).length() < 1e-4,
+                    "{frequency}: {spectrum} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kernel_spectrum_is_odd() {
+        let world_size = GameWorldSize((8, 4));
+        let spectrum = fft_kernel_spectrum(world_size);
+        let at = |x: i32, y: i32| spectrum[(y.rem_euclid(4) * 8 + x.rem_euclid(8)) as usize];
+
+        // mean density, central difference at the Nyquist frequency
+        assert_eq!(at(0, 0), Vec2::ZERO);
+        assert!(at(4, 0).length() < 1e-6, "{}", at(4, 0));
+        // field along x of a wave along x is imaginary
+        let k = PI / 4.0;
+        assert!(at(1, 0).x.abs() < 1e-6);
+        assert!((at(1, 0).y - 2.0 * PI * k.sin() / (k * k)).abs() < 1e-5);
+        for y in 0..4 {
+            for x in 0..8 {
+                assert!(
+                    (at(x, y) + at(-x, -y)).length() < 1e-6,
+                    "{x}, {y}: {} != {}",
+                    at(x, y),
+                    -at(-x, -y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn params_layout_matches_shader() {
+        let shader_defs = GameWorldPipeline::shader_defs(
+            default(),
+            BoundaryMode::default(),
+            GravityMode::Fft,
+            default(),
+        );
+        let module = validate_shader("shaders/fft.wgsl", shader_defs);
+        let layout = StructLayout::of(&module, "FftParams");
+
+        assert_eq!(layout.size, size_of::<FftParams>());
+        assert_eq!(
+            layout.members,
+            [
+                ("source", offset_of!(FftParams, source)),
+                ("axis", offset_of!(FftParams, axis)),
+                ("span", offset_of!(FftParams, span)),
+                ("inverse", offset_of!(FftParams, inverse)),
+            ]
+            .map(|(name, offset)| (name.to_string(), offset))
+        );
+    }
+}
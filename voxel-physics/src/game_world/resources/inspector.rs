@@ -0,0 +1,161 @@
+use std::mem::size_of;
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, renderer::RenderDevice},
+};
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    game_world::{BoundaryMode, CellData, GameWorldSize, INSPECTOR_RADIUS},
+    utils::readback::AsyncReadback,
+};
+
+/// Cell to inspect, the region around it is read back from the GPU every
+/// frame into [`InspectedRegion`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub struct CellInspector {
+    /// Location of the inspected cell, `None` if nothing is inspected.
+    pub location: Option<IVec2>,
+}
+
+/// Cells within [`INSPECTOR_RADIUS`] around the inspected cell.
+///
+/// Read back asynchronously, so the main world copy lags a few frames behind
+/// [`SimulationProgress`](crate::game_world::SimulationProgress).
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct InspectedRegion {
+    pub center: IVec2,
+    /// Step the cells were read back at
+    pub step: u64,
+    /// Cells row by row starting from the top left corner of the region,
+    /// `None` for the cells beyond the world edges. Empty if nothing was read
+    /// back yet.
+    pub cells: Vec<Option<CellData>>,
+}
+
+impl InspectedRegion {
+    /// Side of the square region in cells.
+    pub const SIDE: i32 = INSPECTOR_RADIUS * 2 + 1;
+
+    /// Cell at the given offset from the center of the region.
+    pub fn cell(&self, offset: IVec2) -> Option<&CellData> {
+        if offset.x.abs() > INSPECTOR_RADIUS || offset.y.abs() > INSPECTOR_RADIUS {
+            return None;
+        }
+
+        let slot = (offset.y + INSPECTOR_RADIUS) * Self::SIDE + offset.x + INSPECTOR_RADIUS;
+        self.cells.get(slot as usize)?.as_ref()
+    }
+
+    /// Indices of the cells of the region around `center` in the storage
+    /// buffers in the same order as [`InspectedRegion::cells`], same as
+    /// `get_prev_cell` in `world_data.wgsl` the region wraps around the edges
+    /// only if the world is periodic.
+    pub fn cell_indices(
+        center: IVec2,
+        world_size: GameWorldSize,
+        boundary: BoundaryMode,
+    ) -> Vec<Option<usize>> {
+        let size = IVec2::new(world_size.width() as i32, world_size.height() as i32);
+
+        (-INSPECTOR_RADIUS..=INSPECTOR_RADIUS)
+            .flat_map(|y| (-INSPECTOR_RADIUS..=INSPECTOR_RADIUS).map(move |x| IVec2::new(x, y)))
+            .map(|offset| {
+                let location = center + offset;
+                let in_bounds = location.cmpge(IVec2::ZERO).all() && location.cmplt(size).all();
+                if !in_bounds && boundary != BoundaryMode::Periodic {
+                    return None;
+                }
+
+                let location = location.rem_euclid(size).as_uvec2();
+                Some(world_size.cell_index(location))
+            })
+            .collect()
+    }
+}
+
+/// Buffer the inspected cells are copied into to be mapped, lives in the
+/// render world.
+#[derive(Resource)]
+pub struct InspectorReadback {
+    pub readback: AsyncReadback,
+    /// Region copied into the staging buffer, without the cells.
+    pub region: InspectedRegion,
+    /// Indices of the copied cells, see [`InspectedRegion::cell_indices`].
+    pub cell_indices: Vec<Option<usize>>,
+}
+
+impl FromWorld for InspectorReadback {
+    fn from_world(world: &mut World) -> Self {
+        let slots = (InspectedRegion::SIDE * InspectedRegion::SIDE) as usize;
+        let readback = AsyncReadback::new(
+            world.resource::<RenderDevice>(),
+            "inspector staging buffer",
+            (slots * size_of::<CellData>()) as u64,
+        );
+
+        Self {
+            readback,
+            region: default(),
+            cell_indices: Vec::new(),
+        }
+    }
+}
+
+/// Sends [`InspectedRegion`] from the render world to the main world.
+#[derive(Clone, Debug, Resource)]
+pub struct InspectorSender(pub Sender<InspectedRegion>);
+
+/// Receives [`InspectedRegion`] sent from the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct InspectorReceiver(pub Receiver<InspectedRegion>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD_SIZE: GameWorldSize = GameWorldSize((8, 6));
+
+    #[test]
+    fn region_is_centered_on_the_cell() {
+        let indices =
+            InspectedRegion::cell_indices(IVec2::new(3, 3), WORLD_SIZE, BoundaryMode::Absorb);
+        let region = InspectedRegion {
+            cells: indices
+                .iter()
+                .map(|index| index.map(|index| CellData::new_particle(index as f32, Vec2::ZERO)))
+                .collect(),
+            ..default()
+        };
+
+        assert_eq!(
+            indices.len(),
+            (InspectedRegion::SIDE * InspectedRegion::SIDE) as usize
+        );
+        assert_eq!(region.cell(IVec2::ZERO).unwrap().mass, 27.0);
+        assert_eq!(region.cell(IVec2::new(1, -1)).unwrap().mass, 20.0);
+        assert_eq!(region.cell(IVec2::new(INSPECTOR_RADIUS + 1, 0)), None);
+    }
+
+    #[test]
+    fn region_wraps_around_only_periodic_edges() {
+        let corner = IVec2::ZERO;
+        let top_left = IVec2::splat(-INSPECTOR_RADIUS);
+
+        let periodic = InspectedRegion::cell_indices(corner, WORLD_SIZE, BoundaryMode::Periodic);
+        let (width, height) = WORLD_SIZE.0;
+        let wrapped =
+            (height as i32 - INSPECTOR_RADIUS) * width as i32 + width as i32 - INSPECTOR_RADIUS;
+        assert_eq!(periodic[0], Some(wrapped as usize));
+
+        for boundary in [BoundaryMode::Reflect, BoundaryMode::Absorb] {
+            let indices = InspectedRegion::cell_indices(corner, WORLD_SIZE, boundary);
+            let center = (INSPECTOR_RADIUS * InspectedRegion::SIDE + INSPECTOR_RADIUS) as usize;
+
+            assert_eq!(indices[0], None, "{top_left} is beyond the edges");
+            assert_eq!(indices[center], Some(0));
+        }
+    }
+}
@@ -1,7 +1,25 @@
+pub use config::*;
 pub use controls::*;
 pub use data::*;
+pub use fft::*;
+pub use inspector::*;
 pub use pipelines::*;
+pub use pyramid::*;
+pub use recording::*;
+pub use stats::*;
+pub use transfer::*;
+pub use uniforms::*;
+pub use visualization::*;
 
+mod config;
 mod controls;
 mod data;
+mod fft;
+mod inspector;
 mod pipelines;
+mod pyramid;
+mod recording;
+mod stats;
+mod transfer;
+mod uniforms;
+mod visualization;
@@ -1,27 +1,52 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, mem::size_of};
 
 use bevy::{
     prelude::*,
     render::{extract_resource::ExtractResource, render_resource::*, renderer::RenderDevice},
 };
 
-use crate::game_world::WORLD_SIZE;
+use crossbeam_channel::{Receiver, Sender};
 
-use super::CellData;
+use crate::{game_world::PipelineCompilationFailed, utils::pipeline_state::PipelineStateUtils};
+
+use super::{
+    get_fft_data_size, get_fft_kernel_data_size, get_lost_mass_data_size, get_pyramid_data_size,
+    BoundaryMode, CellData, CollisionMode, FftParams, GameWorldSize, GravityMode, PyramidParams,
+    SimulationParams, StatsPartial, VisualizationParams,
+};
 
 #[derive(Clone, Debug, Resource, ExtractResource)]
 pub struct GameWorldPipeline {
     pub world_bind_group_layout: BindGroupLayout,
+    /// Layout of [`PyramidBindGroups`](crate::game_world::PyramidBindGroups),
+    /// used only by `build_pyramid` on top of the world bind group
+    pub pyramid_bind_group_layout: BindGroupLayout,
+    /// Layout of [`FftBindGroups`](crate::game_world::FftBindGroups), used
+    /// only by the FFT passes on top of the world bind group
+    pub fft_bind_group_layout: BindGroupLayout,
     pub init_pipeline: CachedComputePipelineId,
     pub pre_update_pipeline: CachedComputePipelineId,
     pub update_gravity_pipeline: CachedComputePipelineId,
     pub update_impulse_pipeline: CachedComputePipelineId,
     pub update_position_pipeline: CachedComputePipelineId,
+    pub draw_pipeline: CachedComputePipelineId,
+    pub reduce_stats_pipeline: CachedComputePipelineId,
+    pub build_pyramid_pipeline: CachedComputePipelineId,
+    pub fft_density_pipeline: CachedComputePipelineId,
+    pub fft_butterfly_pipeline: CachedComputePipelineId,
+    pub fft_convolve_pipeline: CachedComputePipelineId,
+    /// Why the gravity mode doesn't support the world, see
+    /// [`GravityMode::check`]
+    pub gravity_error: Option<String>,
 }
 
 impl FromWorld for GameWorldPipeline {
     fn from_world(world: &mut World) -> Self {
-        let data_size = CellData::get_world_data_size(WORLD_SIZE);
+        let world_size = *world.resource::<GameWorldSize>();
+        let boundary = *world.resource::<BoundaryMode>();
+        let gravity = *world.resource::<GravityMode>();
+        let collisions = *world.resource::<CollisionMode>();
+        let data_size = CellData::get_world_data_size(world_size.0);
 
         let data_ty = BindingType::Buffer {
             ty: BufferBindingType::Storage { read_only: false },
@@ -57,68 +82,370 @@ impl FromWorld for GameWorldPipeline {
                             ty: data_ty,
                             count: None,
                         },
+                        BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(SimulationParams::min_size()),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(
+                                    StatsPartial::get_stats_data_size(world_size),
+                                ),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 5,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(get_lost_mass_data_size(
+                                    world_size,
+                                )),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 6,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: Some(VisualizationParams::min_size()),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 7,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(get_pyramid_data_size(
+                                    world_size, gravity,
+                                )),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 8,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(get_fft_data_size(
+                                    world_size, gravity,
+                                )),
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 9,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(get_fft_kernel_data_size(
+                                    world_size, gravity,
+                                )),
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
+        let pyramid_bind_group_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(size_of::<PyramidParams>() as u64),
+                        },
+                        count: None,
+                    }],
+                });
+
+        let fft_bind_group_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(size_of::<FftParams>() as u64),
+                        },
+                        count: None,
+                    }],
+                });
+
         // TODO move assets loading to separate plugin
-        let shader = world
-            .resource::<AssetServer>()
-            .load("shaders/game_world.wgsl");
+        let shader = world.resource::<AssetServer>().load(Self::SHADER);
 
         let pipeline_cache = world.resource::<PipelineCache>();
+        let shader_defs = Self::shader_defs(world_size, boundary, gravity, collisions);
+
+        let queue_pipeline = |entry_point: &'static str| {
+            let mut layout = vec![world_bind_group_layout.clone()];
+            if entry_point == "build_pyramid" {
+                layout.push(pyramid_bind_group_layout.clone());
+            } else if entry_point.starts_with("fft_") {
+                layout.push(fft_bind_group_layout.clone());
+            }
 
-        let init_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: None,
-            layout: vec![world_bind_group_layout.clone()],
-            push_constant_ranges: Vec::new(),
-            shader: shader.clone(),
-            shader_defs: vec![],
-            entry_point: Cow::from("init"),
-        });
-        let pre_update_pipeline =
-            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-                label: None,
-                layout: vec![world_bind_group_layout.clone()],
-                push_constant_ranges: Vec::new(),
-                shader: shader.clone(),
-                shader_defs: vec![],
-                entry_point: Cow::from("pre_update"),
-            });
-        let update_gravity_pipeline =
-            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-                label: None,
-                layout: vec![world_bind_group_layout.clone()],
-                push_constant_ranges: Vec::new(),
-                shader: shader.clone(),
-                shader_defs: vec![],
-                entry_point: Cow::from("update_gravity"),
-            });
-        let update_impulse_pipeline =
             pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
                 label: None,
-                layout: vec![world_bind_group_layout.clone()],
+                layout,
                 push_constant_ranges: Vec::new(),
                 shader: shader.clone(),
-                shader_defs: vec![],
-                entry_point: Cow::from("update_impulse"),
-            });
-        let update_position_pipeline =
-            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-                label: None,
-                layout: vec![world_bind_group_layout.clone()],
-                push_constant_ranges: Vec::new(),
-                shader,
-                shader_defs: vec![],
-                entry_point: Cow::from("update_position"),
-            });
+                shader_defs: shader_defs.clone(),
+                entry_point: Cow::from(entry_point),
+            })
+        };
+
+        let init_pipeline = queue_pipeline("init");
+        let pre_update_pipeline = queue_pipeline("pre_update");
+        let update_gravity_pipeline = queue_pipeline("update_gravity");
+        let update_impulse_pipeline = queue_pipeline("update_impulse");
+        let update_position_pipeline = queue_pipeline("update_position");
+        let draw_pipeline = queue_pipeline("draw");
+        let reduce_stats_pipeline = queue_pipeline("reduce_stats");
+        let build_pyramid_pipeline = queue_pipeline("build_pyramid");
+        let fft_density_pipeline = queue_pipeline("fft_density");
+        let fft_butterfly_pipeline = queue_pipeline("fft_butterfly");
+        let fft_convolve_pipeline = queue_pipeline("fft_convolve");
 
         GameWorldPipeline {
             world_bind_group_layout,
+            pyramid_bind_group_layout,
+            fft_bind_group_layout,
             init_pipeline,
             pre_update_pipeline,
             update_gravity_pipeline,
             update_impulse_pipeline,
             update_position_pipeline,
+            draw_pipeline,
+            reduce_stats_pipeline,
+            build_pyramid_pipeline,
+            fft_density_pipeline,
+            fft_butterfly_pipeline,
+            fft_convolve_pipeline,
+            gravity_error: gravity.check(world_size, boundary).err(),
+        }
+    }
+}
+
+impl GameWorldPipeline {
+    /// Shader with every entry point of the world.
+    pub const SHADER: &'static str = "shaders/game_world.wgsl";
+
+    /// Compute entry points of [`Self::SHADER`], keep in sync with the
+    /// pipelines queued by `from_world`.
+    pub const ENTRY_POINTS: [&'static str; 11] = [
+        "init",
+        "pre_update",
+        "update_gravity",
+        "update_impulse",
+        "update_position",
+        "draw",
+        "reduce_stats",
+        "build_pyramid",
+        "fft_density",
+        "fft_butterfly",
+        "fft_convolve",
+    ];
+
+    /// Shader defs every pipeline is compiled with.
+    pub fn shader_defs(
+        world_size: GameWorldSize,
+        boundary: BoundaryMode,
+        gravity: GravityMode,
+        collisions: CollisionMode,
+    ) -> Vec<ShaderDefVal> {
+        [
+            world_size.shader_defs(),
+            boundary.shader_defs(),
+            gravity.shader_defs(),
+            collisions.shader_defs(),
+        ]
+        .concat()
+    }
+
+    fn pipelines(&self) -> [CachedComputePipelineId; 11] {
+        [
+            self.init_pipeline,
+            self.pre_update_pipeline,
+            self.update_gravity_pipeline,
+            self.update_impulse_pipeline,
+            self.update_position_pipeline,
+            self.draw_pipeline,
+            self.reduce_stats_pipeline,
+            self.build_pyramid_pipeline,
+            self.fft_density_pipeline,
+            self.fft_butterfly_pipeline,
+            self.fft_convolve_pipeline,
+        ]
+    }
+
+    /// Whether all pipelines are compiled and ready to be dispatched.
+    pub fn is_ready(&self, pipeline_cache: &PipelineCache) -> bool {
+        self.gravity_error.is_none()
+            && self
+                .pipelines()
+                .into_iter()
+                .all(|id| pipeline_cache.get_compute_pipeline_state(id).is_ok())
+    }
+
+    /// Pipelines which failed to compile and won't be retried until the
+    /// shader changes, pipelines waiting for the shader to load are not
+    /// errors. `update_gravity` fails if the gravity mode doesn't support the
+    /// world.
+    pub fn errors(&self, pipeline_cache: &PipelineCache) -> Vec<PipelineCompilationFailed> {
+        let gravity_error = self
+            .gravity_error
+            .iter()
+            .map(|message| PipelineCompilationFailed {
+                entry_point: "update_gravity".to_string(),
+                message: message.clone(),
+            });
+
+        let compile_errors = self.pipelines().into_iter().filter_map(|id| {
+            let CachedPipelineState::Err(err) = pipeline_cache.get_compute_pipeline_state(id)
+            else {
+                return None;
+            };
+            // retried once the shader and its imports are loaded
+            if matches!(
+                err,
+                PipelineCacheError::ShaderNotLoaded(_)
+                    | PipelineCacheError::ShaderImportNotYetAvailable
+            ) {
+                return None;
+            }
+
+            let descriptor = pipeline_cache.get_compute_pipeline_descriptor(id);
+            Some(PipelineCompilationFailed {
+                entry_point: descriptor.entry_point.to_string(),
+                message: err.to_string(),
+            })
+        });
+
+        gravity_error.chain(compile_errors).collect()
+    }
+}
+
+/// Pipelines of the world which currently fail to compile, see
+/// [`GameWorldPipeline::errors`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Resource)]
+pub struct PipelineErrors(pub Vec<PipelineCompilationFailed>);
+
+/// Sends [`PipelineErrors`] from the render world to the main world every
+/// time they change.
+#[derive(Clone, Debug, Resource)]
+pub struct PipelineErrorsSender(pub Sender<PipelineErrors>);
+
+/// Receives [`PipelineErrors`] sent from the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct PipelineErrorsReceiver(pub Receiver<PipelineErrors>);
+
+#[cfg(test)]
+mod tests {
+    use naga::ShaderStage;
+
+    use super::*;
+    use crate::utils::shaders::{
+        declared_items, parse_imports, read_shader, shader_paths, validate_shader,
+    };
+
+    #[test]
+    fn imported_items_are_declared() {
+        let shader_defs =
+            GameWorldPipeline::shader_defs(default(), default(), default(), default());
+        let mut missing = Vec::new();
+
+        for path in shader_paths() {
+            for import in parse_imports(&read_shader(&path)) {
+                let module = validate_shader(&import.path, shader_defs.clone());
+                let declared = declared_items(&module);
+                missing.extend(
+                    import
+                        .items
+                        .into_iter()
+                        .filter(|item| !declared.contains(item.as_str()))
+                        .map(|item| format!("{path} imports {item} from {}", import.path)),
+                );
+            }
+        }
+
+        // unused imports of missing items are silently ignored by the composer
+        assert!(missing.is_empty(), "undeclared imports: {missing:#?}");
+    }
+
+    #[test]
+    fn every_shader_is_valid() {
+        let shader_defs =
+            GameWorldPipeline::shader_defs(default(), default(), default(), default());
+
+        for path in shader_paths() {
+            validate_shader(&path, shader_defs.clone());
+        }
+    }
+
+    #[test]
+    fn every_entry_point_is_valid() {
+        let boundaries = [
+            BoundaryMode::Periodic,
+            BoundaryMode::Reflect,
+            BoundaryMode::Absorb,
+        ];
+        let gravities = [GravityMode::Relay, GravityMode::Pyramid, GravityMode::Fft];
+        let collisions = [CollisionMode::Merge, CollisionMode::Bounce];
+
+        for (boundary, gravity, collisions) in boundaries.into_iter().flat_map(|boundary| {
+            gravities.into_iter().flat_map(move |gravity| {
+                collisions.map(|collisions| (boundary, gravity, collisions))
+            })
+        }) {
+            // not a multiple of the workgroup size
+            let world_size = GameWorldSize((100, 60));
+            let module = validate_shader(
+                GameWorldPipeline::SHADER,
+                GameWorldPipeline::shader_defs(world_size, boundary, gravity, collisions),
+            );
+
+            let entry_points: Vec<_> = module
+                .entry_points
+                .iter()
+                .filter(|entry_point| entry_point.stage == ShaderStage::Compute)
+                .map(|entry_point| entry_point.name.as_str())
+                .collect();
+            for entry_point in GameWorldPipeline::ENTRY_POINTS {
+                assert!(
+                    entry_points.contains(&entry_point),
+                    "{} has no compute entry point {entry_point}",
+                    GameWorldPipeline::SHADER
+                );
+            }
         }
     }
 }
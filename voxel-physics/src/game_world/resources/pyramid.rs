@@ -0,0 +1,142 @@
+use std::mem::size_of;
+
+use bevy::{
+    prelude::*,
+    render::{render_resource::*, renderer::RenderDevice},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::game_world::{GameWorldPipeline, GameWorldSize, GravityMode, PYRAMID_TOP_SIZE};
+
+/// Total mass and mass moment of the cells covered by a node of the mass
+/// pyramid, see `pyramid.wgsl`.
+///
+/// Keep in sync with `PyramidNode` in `pyramid.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PyramidNode {
+    /// Sum of particle positions weighted by mass
+    pub mass_moment: Vec2,
+    pub mass: f32,
+    /// WGSL rounds the size of the struct up to the alignment of `vec2`
+    pub _padding: u32,
+}
+
+/// Uniform selecting the level written by `build_pyramid`.
+///
+/// Keep in sync with `PyramidParams` in `pyramid.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct PyramidParams {
+    pub level: u32,
+}
+
+/// Size of every level of the mass pyramid in nodes, from the base level with
+/// a node per cell up to the top level, same as `level_size` and `top_level`
+/// in `pyramid.wgsl`.
+pub fn pyramid_level_sizes(world_size: GameWorldSize) -> Vec<(u32, u32)> {
+    let mut sizes = vec![world_size.0];
+    while let Some(&(width, height)) = sizes
+        .last()
+        .filter(|(width, height)| *width > PYRAMID_TOP_SIZE || *height > PYRAMID_TOP_SIZE)
+    {
+        sizes.push((width.div_ceil(2), height.div_ceil(2)));
+    }
+    sizes
+}
+
+/// Size of [`GameWorldData::pyramid`](crate::game_world::GameWorldData::pyramid)
+/// buffer, a single unused node without pyramid gravity.
+pub fn get_pyramid_data_size(world_size: GameWorldSize, gravity: GravityMode) -> u64 {
+    let nodes = match gravity {
+        GravityMode::Relay | GravityMode::Fft => 1,
+        GravityMode::Pyramid => pyramid_level_sizes(world_size)
+            .into_iter()
+            .map(|(width, height)| (width * height) as usize)
+            .sum(),
+    };
+    (nodes * size_of::<PyramidNode>()) as u64
+}
+
+/// Bind groups of [`PyramidParams`] for every level of the mass pyramid,
+/// empty without pyramid gravity. Lives in the render world.
+#[derive(Clone, Debug, Resource, Deref)]
+pub struct PyramidBindGroups(pub Vec<BindGroup>);
+
+impl FromWorld for PyramidBindGroups {
+    fn from_world(world: &mut World) -> Self {
+        if *world.resource::<GravityMode>() != GravityMode::Pyramid {
+            return Self(Vec::new());
+        }
+
+        let levels = pyramid_level_sizes(*world.resource::<GameWorldSize>()).len();
+        let layout = &world
+            .resource::<GameWorldPipeline>()
+            .pyramid_bind_group_layout;
+        let render_device = world.resource::<RenderDevice>();
+
+        let bind_groups = (0..levels as u32)
+            .map(|level| {
+                let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some("pyramid_params"),
+                    contents: bytemuck::bytes_of(&PyramidParams { level }),
+                    usage: BufferUsages::UNIFORM,
+                });
+                render_device.create_bind_group(
+                    Some("pyramid_bind_group"),
+                    layout,
+                    &BindGroupEntries::single(params.as_entire_binding()),
+                )
+            })
+            .collect();
+
+        Self(bind_groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::{
+        game_world::BoundaryMode,
+        utils::shaders::{validate_shader, StructLayout},
+    };
+
+    #[test]
+    fn levels_halve_up_to_the_top() {
+        assert_eq!(
+            pyramid_level_sizes(GameWorldSize((1024, 1024))).last(),
+            Some(&(4, 4))
+        );
+        assert_eq!(pyramid_level_sizes(GameWorldSize((1024, 1024))).len(), 9);
+        assert_eq!(
+            pyramid_level_sizes(GameWorldSize((37, 5))),
+            [(37, 5), (19, 3), (10, 2), (5, 1), (3, 1)]
+        );
+        assert_eq!(pyramid_level_sizes(GameWorldSize((3, 4))), [(3, 4)]);
+    }
+
+    #[test]
+    fn node_layout_matches_shader() {
+        let shader_defs = GameWorldPipeline::shader_defs(
+            default(),
+            BoundaryMode::default(),
+            GravityMode::Pyramid,
+            default(),
+        );
+        let module = validate_shader("shaders/pyramid.wgsl", shader_defs);
+        let layout = StructLayout::of(&module, "PyramidNode");
+
+        assert_eq!(layout.size, size_of::<PyramidNode>());
+        assert_eq!(
+            layout.members,
+            [
+                ("mass_moment", offset_of!(PyramidNode, mass_moment)),
+                ("mass", offset_of!(PyramidNode, mass)),
+            ]
+            .map(|(name, offset)| (name.to_string(), offset))
+        );
+    }
+}
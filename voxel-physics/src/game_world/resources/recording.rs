@@ -0,0 +1,246 @@
+use std::path::PathBuf;
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, renderer::RenderDevice},
+};
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    game_world::{frame::WorldFrame, GameWorldSize, DEFAULT_RECORDING_INTERVAL, FRAME_READBACKS},
+    utils::readback::{padded_row_size, AsyncReadback},
+};
+
+/// Writes the world texture as a numbered sequence of PNG images while the
+/// simulation runs, e.g. to make videos in the native resolution of the world.
+#[derive(Clone, Debug, PartialEq, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub struct FrameRecording {
+    /// Directory the frames are written to, `None` if not recording. Frames
+    /// are numbered from `000000.png` every time it changes.
+    pub directory: Option<PathBuf>,
+    /// Steps between recorded frames, frames are recorded at every multiple
+    /// of it
+    pub interval: u64,
+}
+
+impl Default for FrameRecording {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            interval: DEFAULT_RECORDING_INTERVAL,
+        }
+    }
+}
+
+/// Frames recorded so far, lives in the render world.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct FrameRecorder {
+    /// Directory of the current recording
+    pub directory: Option<PathBuf>,
+    /// Number of frames recorded into `directory`
+    pub frames: u32,
+    /// Step of the last recorded frame
+    pub last_step: Option<u64>,
+}
+
+impl FrameRecorder {
+    /// Path of the frame to record at the given step, `None` if the frame
+    /// should be skipped.
+    pub fn next_frame(&mut self, recording: &FrameRecording, step: u64) -> Option<PathBuf> {
+        let Some(directory) = &recording.directory else {
+            *self = default();
+            return None;
+        };
+
+        if self.directory.as_ref() != Some(directory) {
+            *self = Self {
+                directory: Some(directory.clone()),
+                ..default()
+            };
+        }
+
+        // once at every multiple of the interval, the world could have been
+        // reset since the last frame
+        if !step.is_multiple_of(recording.interval.max(1)) || self.last_step == Some(step) {
+            return None;
+        }
+
+        let path = directory.join(format!("{:06}.png", self.frames));
+        self.frames += 1;
+        self.last_step = Some(step);
+
+        Some(path)
+    }
+
+    /// Max number of steps the simulation can advance from `step` without
+    /// skipping a frame, `None` if not recording. Zero until the frame of
+    /// `step` is recorded, e.g. while all readbacks are busy.
+    pub fn steps_to_next_frame(&self, recording: &FrameRecording, step: u64) -> Option<u64> {
+        recording.directory.as_ref()?;
+
+        let interval = recording.interval.max(1);
+        let recorded = self.directory == recording.directory && self.last_step == Some(step);
+        if step.is_multiple_of(interval) && !recorded {
+            return Some(0);
+        }
+
+        Some(interval - step % interval)
+    }
+}
+
+/// Paths to save frames of the world texture to, handed over to the render
+/// world every frame.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct FrameRequests(pub Vec<PathBuf>);
+
+/// Buffer a frame of the world texture is copied into to be mapped.
+pub struct FrameReadback {
+    pub readback: AsyncReadback,
+    /// Step of the frame copied into the staging buffer.
+    pub step: u64,
+    /// Paths to save the frame to.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Pool of [`FrameReadback`]s, so several frames can be read back at the same
+/// time while recording. Lives in the render world.
+#[derive(Default, Resource)]
+pub struct FrameReadbacks {
+    pub readbacks: Vec<FrameReadback>,
+    /// Requested frames waiting for a free readback
+    pub pending: Vec<PathBuf>,
+}
+
+impl FrameReadbacks {
+    /// Whether a frame can be reserved right now.
+    pub fn has_free(&self) -> bool {
+        self.readbacks.len() < FRAME_READBACKS
+            || self.readbacks.iter().any(|frame| frame.readback.is_free())
+    }
+
+    /// Reserves a free readback for the frame of `step`, a new one is created
+    /// if none is free and the pool is not full yet.
+    pub fn reserve(
+        &mut self,
+        render_device: &RenderDevice,
+        world_size: GameWorldSize,
+        step: u64,
+        paths: Vec<PathBuf>,
+    ) -> Option<&mut FrameReadback> {
+        let index = match self
+            .readbacks
+            .iter()
+            .position(|frame| frame.readback.is_free())
+        {
+            Some(index) => index,
+            None if self.readbacks.len() < FRAME_READBACKS => {
+                let size = padded_row_size(world_size.width()) * world_size.height();
+                self.readbacks.push(FrameReadback {
+                    readback: AsyncReadback::new(
+                        render_device,
+                        "frame staging buffer",
+                        size as u64,
+                    ),
+                    step: 0,
+                    paths: Vec::new(),
+                });
+                self.readbacks.len() - 1
+            }
+            None => return None,
+        };
+
+        let frame = &mut self.readbacks[index];
+        frame.readback.reserve();
+        frame.step = step;
+        frame.paths = paths;
+        Some(frame)
+    }
+}
+
+/// Sends frames read back in the render world to the main world, or why they
+/// couldn't be read back.
+#[derive(Clone, Debug, Resource)]
+pub struct FrameSender(pub Sender<(PathBuf, Result<WorldFrame, String>)>);
+
+/// Receives frames read back in the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct FrameReceiver(pub Receiver<(PathBuf, Result<WorldFrame, String>)>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(directory: &str, interval: u64) -> FrameRecording {
+        FrameRecording {
+            directory: Some(directory.into()),
+            interval,
+        }
+    }
+
+    #[test]
+    fn frames_are_recorded_every_interval() {
+        let recording = recording("frames", 10);
+        let mut recorder = FrameRecorder::default();
+
+        let recorded: Vec<_> = [0, 0, 5, 10, 13, 20, 40]
+            .into_iter()
+            .filter_map(|step| recorder.next_frame(&recording, step))
+            .collect();
+
+        assert_eq!(
+            recorded,
+            ["000000.png", "000001.png", "000002.png", "000003.png"]
+                .map(|name| PathBuf::from("frames").join(name))
+        );
+        assert_eq!(recorder.last_step, Some(40));
+    }
+
+    #[test]
+    fn steps_stop_at_every_frame() {
+        let recording = recording("frames", 10);
+        let mut recorder = FrameRecorder::default();
+
+        assert_eq!(
+            recorder.steps_to_next_frame(&FrameRecording::default(), 0),
+            None
+        );
+        assert_eq!(recorder.steps_to_next_frame(&recording, 3), Some(7));
+        // waits for the frame to be recorded
+        assert_eq!(recorder.steps_to_next_frame(&recording, 10), Some(0));
+        recorder.next_frame(&recording, 10);
+        assert_eq!(recorder.steps_to_next_frame(&recording, 10), Some(10));
+        // a new recording starts with a frame
+        assert_eq!(
+            recorder.steps_to_next_frame(&self::recording("other", 10), 10),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn numbering_restarts_with_new_directory() {
+        let mut recorder = FrameRecorder::default();
+
+        recorder.next_frame(&recording("first", 1), 0);
+        recorder.next_frame(&recording("first", 1), 1);
+        assert_eq!(recorder.next_frame(&FrameRecording::default(), 2), None);
+
+        assert_eq!(
+            recorder.next_frame(&recording("second", 1), 3),
+            Some(PathBuf::from("second").join("000000.png"))
+        );
+    }
+
+    #[test]
+    fn frame_is_recorded_after_reset() {
+        let recording = recording("frames", 10);
+        let mut recorder = FrameRecorder::default();
+
+        recorder.next_frame(&recording, 100);
+
+        assert_eq!(
+            recorder.next_frame(&recording, 0),
+            Some(PathBuf::from("frames").join("000001.png"))
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::mem::size_of;
+
+use bevy::{math::DVec2, prelude::*, render::renderer::RenderDevice};
+use bytemuck::{Pod, Zeroable};
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    game_world::{
+        CellData, GameWorldSize, DEFAULT_STEPS_PER_FRAME, PARTICLE_NOTHING, STATS_READBACKS,
+    },
+    utils::readback::AsyncReadback,
+};
+
+/// Totals over all particles of the world, used to check that the simulation
+/// conserves mass and momentum.
+///
+/// Reduced on the GPU by `reduce_stats` pass after every step and read back
+/// asynchronously, so the main world copy lags a few frames behind
+/// [`SimulationProgress`]. Stats of every step are sent as
+/// [`SimulationStatsUpdated`] events.
+///
+/// [`SimulationProgress`]: crate::game_world::SimulationProgress
+/// [`SimulationStatsUpdated`]: crate::game_world::SimulationStatsUpdated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SimulationStats {
+    /// Step the stats were computed at
+    pub step: u64,
+    pub particle_count: u64,
+    pub total_mass: f64,
+    /// Sum of `impulse` of all particles
+    pub total_momentum: DVec2,
+    /// Average position of particles weighted by mass, in cells. Positions are
+    /// not wrapped around the world edges.
+    pub center_of_mass: DVec2,
+    /// Mass absorbed by the edges since the world was initialized, see
+    /// [`BoundaryMode::Absorb`](crate::game_world::BoundaryMode::Absorb)
+    pub lost_mass: f64,
+}
+
+impl SimulationStats {
+    /// Adds up partial sums in double precision.
+    pub fn from_partials<'a>(
+        step: u64,
+        partials: impl IntoIterator<Item = &'a StatsPartial>,
+    ) -> Self {
+        let mut stats = Self { step, ..default() };
+        let mut mass_moment = DVec2::ZERO;
+
+        for partial in partials {
+            stats.particle_count += partial.particle_count as u64;
+            stats.total_mass += partial.mass as f64;
+            stats.total_momentum += partial.momentum.as_dvec2();
+            mass_moment += partial.mass_moment.as_dvec2();
+            stats.lost_mass += partial.lost_mass as f64;
+        }
+
+        if stats.total_mass > 0.0 {
+            stats.center_of_mass = mass_moment / stats.total_mass;
+        }
+
+        stats
+    }
+
+    /// Same as `reduce_stats` entry point followed by
+    /// [`SimulationStats::from_partials`], but with a partial sum per cell.
+    pub fn from_cells(
+        step: u64,
+        world_size: (u32, u32),
+        cells: &[CellData],
+        lost_mass: &[f32],
+    ) -> Self {
+        let world_size = GameWorldSize(world_size);
+        let partials: Vec<_> = (0..world_size.height())
+            .flat_map(|y| (0..world_size.width()).map(move |x| UVec2::new(x, y)))
+            .map(|location| {
+                let index = world_size.cell_index(location);
+                StatsPartial::of_cell(&cells[index], location, lost_mass[index])
+            })
+            .collect();
+
+        Self::from_partials(step, &partials)
+    }
+}
+
+/// Stats of a part of the world.
+///
+/// Keep in sync with `StatsPartial` in `stats.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct StatsPartial {
+    pub mass: f32,
+    pub particle_count: u32,
+    pub momentum: Vec2,
+    /// Sum of particle positions weighted by mass
+    pub mass_moment: Vec2,
+    /// Mass absorbed by the edges
+    pub lost_mass: f32,
+    /// WGSL rounds the size of the struct up to the alignment of `vec2`
+    pub _padding: u32,
+}
+
+impl StatsPartial {
+    /// Same as `cell_stats` in `stats.wgsl`
+    pub fn of_cell(cell: &CellData, location: UVec2, lost_mass: f32) -> Self {
+        let stats = Self {
+            lost_mass,
+            ..default()
+        };
+
+        if cell.particle_type == PARTICLE_NOTHING {
+            return stats;
+        }
+
+        let position = location.as_vec2() + cell.relative_pos;
+        Self {
+            mass: cell.mass,
+            particle_count: 1,
+            momentum: cell.impulse,
+            mass_moment: position * cell.mass,
+            ..stats
+        }
+    }
+
+    /// Size of the buffer holding partial sums of all workgroups.
+    pub fn get_stats_data_size(world_size: GameWorldSize) -> u64 {
+        let (workgroups_x, workgroups_y) = world_size.workgroups();
+        ((workgroups_x * workgroups_y) as usize * size_of::<Self>()) as u64
+    }
+}
+
+/// Buffer the partial sums of the steps of a frame are copied into to be
+/// mapped, one after another.
+pub struct StatsReadback {
+    pub readback: AsyncReadback,
+    /// Max number of steps the staging buffer holds.
+    capacity: usize,
+    /// Steps of the stats copied into the staging buffer.
+    pub steps: Vec<u64>,
+}
+
+/// Pool of [`StatsReadback`]s, so stats of several frames can be read back at
+/// the same time. Lives in the render world.
+#[derive(Default, Resource)]
+pub struct StatsReadbacks(pub Vec<StatsReadback>);
+
+impl StatsReadbacks {
+    /// Reserves a free readback for the stats of `steps`, a new one is
+    /// created if none is free and the pool is not full yet. Returns its
+    /// index.
+    pub fn reserve(
+        &mut self,
+        render_device: &RenderDevice,
+        world_size: GameWorldSize,
+        steps: Vec<u64>,
+    ) -> Option<usize> {
+        let free = self.0.iter().position(|stats| stats.readback.is_free());
+        let index = match free {
+            Some(index) if self.0[index].capacity >= steps.len() => index,
+            // too small for this frame
+            Some(index) => {
+                self.0[index] = Self::create(render_device, world_size, steps.len());
+                index
+            }
+            None if self.0.len() < STATS_READBACKS => {
+                let capacity = steps.len().max(DEFAULT_STEPS_PER_FRAME as usize);
+                self.0
+                    .push(Self::create(render_device, world_size, capacity));
+                self.0.len() - 1
+            }
+            None => return None,
+        };
+
+        let stats = &mut self.0[index];
+        stats.readback.reserve();
+        stats.steps = steps;
+        Some(index)
+    }
+
+    fn create(
+        render_device: &RenderDevice,
+        world_size: GameWorldSize,
+        capacity: usize,
+    ) -> StatsReadback {
+        let readback = AsyncReadback::new(
+            render_device,
+            "stats staging buffer",
+            StatsPartial::get_stats_data_size(world_size) * capacity as u64,
+        );
+
+        StatsReadback {
+            readback,
+            capacity,
+            steps: Vec::new(),
+        }
+    }
+}
+
+/// Sends [`SimulationStats`] from the render world to the main world.
+#[derive(Clone, Debug, Resource)]
+pub struct StatsSender(pub Sender<SimulationStats>);
+
+/// Receives [`SimulationStats`] sent from the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct StatsReceiver(pub Receiver<SimulationStats>);
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::{
+        game_world::GameWorldPipeline,
+        utils::shaders::{validate_shader, StructLayout},
+    };
+
+    #[test]
+    fn stats_of_cells() {
+        let size = (4, 4);
+        let mut cells = vec![CellData::new_empty(); 16];
+        cells[0] = CellData::new_particle(1.0, Vec2::new(1.0, 0.0));
+        cells[15] = CellData::new_particle(3.0, Vec2::new(0.0, -1.0));
+
+        let mut lost_mass = vec![0.0; 16];
+        lost_mass[3] = 0.5;
+
+        let stats = SimulationStats::from_cells(7, size, &cells, &lost_mass);
+
+        assert_eq!(stats.step, 7);
+        assert_eq!(stats.particle_count, 2);
+        assert_eq!(stats.total_mass, 4.0);
+        assert_eq!(stats.total_momentum, DVec2::new(1.0, -3.0));
+        assert_eq!(stats.center_of_mass, DVec2::new(2.25, 2.25));
+        assert_eq!(stats.lost_mass, 0.5);
+    }
+
+    #[test]
+    fn empty_world_has_no_center_of_mass() {
+        let cells = vec![CellData::new_empty(); 16];
+
+        let stats = SimulationStats::from_cells(0, (4, 4), &cells, &[0.0; 16]);
+
+        assert_eq!(stats.particle_count, 0);
+        assert_eq!(stats.center_of_mass, DVec2::ZERO);
+    }
+
+    #[test]
+    fn partial_layout_matches_shader() {
+        let shader_defs =
+            GameWorldPipeline::shader_defs(default(), default(), default(), default());
+        let module = validate_shader("shaders/stats.wgsl", shader_defs);
+        let layout = StructLayout::of(&module, "StatsPartial");
+
+        assert_eq!(layout.size, size_of::<StatsPartial>());
+        assert_eq!(
+            layout.members,
+            [
+                ("mass", offset_of!(StatsPartial, mass)),
+                ("particle_count", offset_of!(StatsPartial, particle_count)),
+                ("momentum", offset_of!(StatsPartial, momentum)),
+                ("mass_moment", offset_of!(StatsPartial, mass_moment)),
+                ("lost_mass", offset_of!(StatsPartial, lost_mass)),
+            ]
+            .map(|(name, offset)| (name.to_string(), offset))
+        );
+    }
+}
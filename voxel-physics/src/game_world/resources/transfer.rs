@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::game_world::{scenario::Scenario, snapshot::WorldSnapshot, CellData};
+
+/// Pending writes into the storage buffers, handed over to the render world
+/// every frame.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct GameWorldUploads {
+    /// Re-runs `init` pass and resets the step counter. Cells uploaded during
+    /// the same frame are overwritten by `init`, a snapshot skips it.
+    pub reinitialize: bool,
+    /// Replaces the whole world state, `init` pass is skipped if it wasn't
+    /// dispatched yet.
+    pub snapshot: Option<WorldSnapshot>,
+    /// Single cells to overwrite, written after the snapshot.
+    pub cells: Vec<(UVec2, CellData)>,
+}
+
+/// Scenario the world was loaded from, uploaded again instead of running
+/// `init` pass when the world is reinitialized.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct LoadedScenario(pub Option<Scenario>);
+
+/// Paths to save snapshots of the current world state to, handed over to the
+/// render world every frame.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct SnapshotRequests(pub Vec<PathBuf>);
+
+/// Sends snapshots read back in the render world to the main world, or why
+/// they couldn't be read back.
+#[derive(Clone, Debug, Resource)]
+pub struct SnapshotSender(pub Sender<(PathBuf, Result<WorldSnapshot, String>)>);
+
+/// Receives snapshots read back in the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct SnapshotReceiver(pub Receiver<(PathBuf, Result<WorldSnapshot, String>)>);
+
+/// Progress of the simulation.
+///
+/// Updated by `GameWorldNode` in the render world and sent back to the main
+/// world every frame, so the main world copy lags a frame or two behind.
+#[derive(Clone, Copy, Debug, Default, Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SimulationProgress {
+    /// Whether the world state is already initialized, either by `init` pass
+    /// or by an upload.
+    pub initialized: bool,
+    /// Number of full simulation steps done.
+    pub step: u64,
+}
+
+/// Sends [`SimulationProgress`] from the render world to the main world.
+#[derive(Clone, Debug, Resource)]
+pub struct ProgressSender(pub Sender<SimulationProgress>);
+
+/// Receives [`SimulationProgress`] sent from the render world.
+#[derive(Clone, Debug, Resource)]
+pub struct ProgressReceiver(pub Receiver<SimulationProgress>);
@@ -0,0 +1,83 @@
+//! Structs uploaded as uniforms through the `ShaderType` derive.
+//!
+//! The derive generates a check function per field next to the struct, which
+//! newer compilers report as never used. An allow on the struct doesn't reach
+//! them, so every derived struct is kept in its own module allowing it.
+
+use crate::game_world::{
+    DEFAULT_GRAVITY_CONSTANT, DEFAULT_MASS, DEFAULT_MAX_SPEED, DEFAULT_MERGE_SPEED,
+    DEFAULT_PARTICLE_CHANCE, DEFAULT_RESTITUTION, DEFAULT_SEED, DEFAULT_STEP_DURATION,
+};
+
+pub use simulation_params::SimulationParams;
+pub use visualization_params::VisualizationParams;
+
+#[allow(dead_code)]
+mod simulation_params {
+    use bevy::{
+        prelude::*,
+        render::{extract_resource::ExtractResource, render_resource::ShaderType},
+    };
+
+    /// Simulation parameters, uploaded every frame as `params` uniform.
+    ///
+    /// Keep in sync with `SimulationParams` in `world_data.wgsl`
+    #[derive(Clone, Copy, Debug, PartialEq, Resource, Reflect, ExtractResource, ShaderType)]
+    #[reflect(Resource)]
+    pub struct SimulationParams {
+        /// Duration of a single simulation step
+        pub step_duration: f32,
+        pub gravity_constant: f32,
+        /// Chance of a cell to be filled with a particle by `init`
+        pub particle_chance: f32,
+        /// Mass of particles spawned by `init`
+        pub default_mass: f32,
+        /// Max distance in cells particle can travel during a single step.
+        /// Particles never move further than one cell per step, so values above
+        /// `1.0` only affect the direction of movement.
+        pub max_speed: f32,
+        /// Seed of the random distribution generated by `init`, the same seed
+        /// always produces the same world.
+        pub seed: u32,
+        /// Part of the approach speed left after particles bounce off each
+        /// other with [`CollisionMode::Bounce`](crate::game_world::CollisionMode::Bounce),
+        /// `0.0` stops them along the line between their cells and `1.0` is a
+        /// perfectly elastic collision.
+        pub restitution: f32,
+        /// Colliding particles merge if every pair of them approaches slower than
+        /// this, in cells per step.
+        pub merge_speed: f32,
+    }
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        Self {
+            step_duration: DEFAULT_STEP_DURATION,
+            gravity_constant: DEFAULT_GRAVITY_CONSTANT,
+            particle_chance: DEFAULT_PARTICLE_CHANCE,
+            default_mass: DEFAULT_MASS,
+            max_speed: DEFAULT_MAX_SPEED,
+            seed: DEFAULT_SEED,
+            restitution: DEFAULT_RESTITUTION,
+            merge_speed: DEFAULT_MERGE_SPEED,
+        }
+    }
+}
+
+#[allow(dead_code)]
+mod visualization_params {
+    use bevy::render::render_resource::ShaderType;
+
+    /// [`VisualizationSettings`](crate::game_world::VisualizationSettings)
+    /// uploaded as `visualization` uniform.
+    ///
+    /// Keep in sync with `VisualizationParams` in `visualization.wgsl`
+    #[derive(Clone, Copy, Debug, Default, PartialEq, ShaderType)]
+    pub struct VisualizationParams {
+        pub mode: u32,
+        pub max_mass: f32,
+        pub max_field: f32,
+        pub max_speed: f32,
+    }
+}
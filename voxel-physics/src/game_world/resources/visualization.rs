@@ -0,0 +1,86 @@
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
+
+use crate::game_world::{
+    VisualizationParams, DEFAULT_COLOR_MAX_FIELD, DEFAULT_COLOR_MAX_MASS, DEFAULT_COLOR_MAX_SPEED,
+};
+
+/// What the world texture shows.
+///
+/// Keep in sync with `VISUALIZATION_*` in `visualization.wgsl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[repr(u32)]
+pub enum VisualizationMode {
+    /// Gravity direction for empty cells and impulse for particles
+    #[default]
+    Combined = 0,
+    /// Particle mass on a log scale up to
+    /// [`VisualizationSettings::max_mass`], viridis colormap
+    MassDensity = 1,
+    /// Field strength on a log scale up to
+    /// [`VisualizationSettings::max_field`], magma colormap
+    GravityStrength = 2,
+    /// Field direction as hue and strength as brightness
+    GravityDirection = 3,
+    /// Particle speed up to [`VisualizationSettings::max_speed`], magma
+    /// colormap
+    Velocity = 4,
+    /// Flat color per particle type
+    ParticleType = 5,
+}
+
+impl VisualizationMode {
+    pub const ALL: [Self; 6] = [
+        Self::Combined,
+        Self::MassDensity,
+        Self::GravityStrength,
+        Self::GravityDirection,
+        Self::Velocity,
+        Self::ParticleType,
+    ];
+
+    /// The next mode, wraps around after the last one.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// The previous mode, wraps around before the first one.
+    pub fn prev(self) -> Self {
+        Self::ALL[(self as usize + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// How the world is drawn into the texture, can be changed at any time.
+#[derive(Clone, Copy, Debug, PartialEq, Resource, Reflect, ExtractResource)]
+#[reflect(Resource)]
+pub struct VisualizationSettings {
+    pub mode: VisualizationMode,
+    /// Mass at the top of the mass density colormap
+    pub max_mass: f32,
+    /// Field strength at the top of the gravity colormaps, the colormaps
+    /// cover 4 decades below it
+    pub max_field: f32,
+    /// Speed in cells per step at the top of the velocity colormap
+    pub max_speed: f32,
+}
+
+impl Default for VisualizationSettings {
+    fn default() -> Self {
+        Self {
+            mode: VisualizationMode::default(),
+            max_mass: DEFAULT_COLOR_MAX_MASS,
+            max_field: DEFAULT_COLOR_MAX_FIELD,
+            max_speed: DEFAULT_COLOR_MAX_SPEED,
+        }
+    }
+}
+
+impl From<VisualizationSettings> for VisualizationParams {
+    fn from(settings: VisualizationSettings) -> Self {
+        Self {
+            mode: settings.mode as u32,
+            max_mass: settings.max_mass,
+            max_field: settings.max_field,
+            max_speed: settings.max_speed,
+        }
+    }
+}
@@ -0,0 +1,611 @@
+//! Hand-crafted initial conditions of the world.
+//!
+//! Scenarios are [RON](https://github.com/ron-rs/ron) files listing objects,
+//! which are rasterized into [`CellData`] and uploaded instead of running
+//! `init` pass. Positions are in cells and wrap around the edges of periodic
+//! worlds, particles outside of other worlds are dropped:
+//!
+//! ```ron
+//! (
+//!     objects: [
+//!         Body(position: (512.0, 512.0), mass: 100.0),
+//!         RandomFill(min: (0.0, 0.0), max: (128.0, 128.0), density: 0.01, mass: 1.0),
+//!         Disk(
+//!             center: (512.0, 512.0),
+//!             inner_radius: 16.0,
+//!             outer_radius: 64.0,
+//!             density: 0.05,
+//!             mass: 1.0,
+//!             central_mass: 100.0,
+//!         ),
+//!         MassMap(path: "galaxy.png", position: (256.0, 256.0), mass: 2.0),
+//!     ],
+//! )
+//! ```
+//!
+//! A PNG image can be loaded as a scenario too, it is the same as a single
+//! `MassMap` at the origin with [`DEFAULT_MASS_MAP_MASS`], see
+//! [`mass_map`](crate::game_world::mass_map) for how pixels map to particles.
+//!
+//! Particles landing in the same cell are merged, the same way
+//! `update_position` merges them.
+
+use std::{
+    f32::consts::PI,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game_world::{
+    cpu::{hash, random_float_seeded},
+    mass_map::MassMap,
+    BoundaryMode, CellData, SimulationParams, DEFAULT_MASS_MAP_MASS, PARTICLE_NOTHING,
+};
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Image(PathBuf, image::ImageError),
+    /// Object at the index has values which would make invalid particles
+    InvalidObject {
+        index: usize,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Parse(err) => write!(f, "parse error: {err}"),
+            Self::Image(path, err) => write!(f, "image error in {}: {err}", path.display()),
+            Self::InvalidObject { index, reason } => write!(f, "invalid object {index}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Image(_, err) => Some(err),
+            Self::InvalidObject { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for ScenarioError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub objects: Vec<ScenarioObject>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScenarioObject {
+    /// Single particle
+    Body {
+        position: Vec2,
+        mass: f32,
+        #[serde(default)]
+        velocity: Vec2,
+    },
+    /// Rectangle from `min` to `max` randomly filled with resting particles
+    RandomFill {
+        min: Vec2,
+        max: Vec2,
+        /// Chance of a cell to be filled with a particle
+        density: f32,
+        /// Mass of every particle
+        mass: f32,
+    },
+    /// Ring of particles on circular orbits around `center`, a disk if
+    /// `inner_radius` is zero
+    Disk {
+        center: Vec2,
+        inner_radius: f32,
+        outer_radius: f32,
+        /// Chance of a cell to be filled with a particle
+        density: f32,
+        /// Mass of every particle
+        mass: f32,
+        /// Mass of the body placed at `center`, none is placed if zero
+        #[serde(default)]
+        central_mass: f32,
+        #[serde(default)]
+        clockwise: bool,
+    },
+    /// Particles drawn in a PNG image, see
+    /// [`mass_map`](crate::game_world::mass_map)
+    MassMap {
+        /// Relative to the scenario file
+        path: PathBuf,
+        /// Cell of the top left pixel
+        #[serde(default)]
+        position: Vec2,
+        /// Mass of a white pixel
+        mass: f32,
+        /// Speed encoded by the red and green channels, the image holds only
+        /// the mass if zero
+        #[serde(default)]
+        max_speed: f32,
+        /// Loaded by [`Scenario::load`], objects without image are skipped
+        #[serde(skip)]
+        image: Option<MassMap>,
+    },
+}
+
+impl Scenario {
+    pub fn from_ron(source: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = ron::from_str(source)?;
+        for (index, object) in scenario.objects.iter().enumerate() {
+            object
+                .validate()
+                .map_err(|reason| ScenarioError::InvalidObject { index, reason })?;
+        }
+
+        Ok(scenario)
+    }
+
+    /// Loads a RON scenario or a PNG mass map, depending on the extension,
+    /// along with all mass maps it refers to.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+
+        let (mut scenario, directory) = if is_png {
+            (Self::from_mass_map(path), Path::new(""))
+        } else {
+            let scenario = Self::from_ron(&fs::read_to_string(path)?)?;
+            (scenario, path.parent().unwrap_or(Path::new("")))
+        };
+
+        for object in &mut scenario.objects {
+            if let ScenarioObject::MassMap { path, image, .. } = object {
+                let path = directory.join(&*path);
+                let mass_map =
+                    MassMap::load(&path).map_err(|err| ScenarioError::Image(path, err))?;
+                *image = Some(mass_map);
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    /// Scenario made of a single mass map at the origin, the image is not
+    /// loaded.
+    pub fn from_mass_map(path: impl Into<PathBuf>) -> Self {
+        Self {
+            objects: vec![ScenarioObject::MassMap {
+                path: path.into(),
+                position: Vec2::ZERO,
+                mass: DEFAULT_MASS_MAP_MASS,
+                max_speed: 0.0,
+                image: None,
+            }],
+        }
+    }
+
+    /// Rasterizes all objects into cells of the world of the given size.
+    ///
+    /// Random fills use [`SimulationParams::seed`] and orbital velocities use
+    /// [`SimulationParams::gravity_constant`].
+    pub fn rasterize(
+        &self,
+        size: (u32, u32),
+        boundary: BoundaryMode,
+        params: &SimulationParams,
+    ) -> Vec<CellData> {
+        let mut raster = Raster {
+            size,
+            boundary,
+            cells: vec![CellData::new_empty(); (size.0 * size.1) as usize],
+            dropped: 0,
+        };
+
+        for (object_index, object) in self.objects.iter().enumerate() {
+            // every object gets its own distribution
+            let seed = hash(params.seed ^ object_index as u32);
+
+            match *object {
+                ScenarioObject::Body {
+                    position,
+                    mass,
+                    velocity,
+                } => {
+                    raster.add_particle(position.round().as_ivec2(), mass, velocity);
+                }
+                ScenarioObject::RandomFill {
+                    min,
+                    max,
+                    density,
+                    mass,
+                } => {
+                    for location in cells_between(min, max) {
+                        if raster.random_float(seed, location) < density {
+                            raster.add_particle(location, mass, Vec2::ZERO);
+                        }
+                    }
+                }
+                ScenarioObject::Disk {
+                    center,
+                    inner_radius,
+                    outer_radius,
+                    density,
+                    mass,
+                    central_mass,
+                    clockwise,
+                } => {
+                    if central_mass > 0.0 {
+                        raster.add_particle(center.round().as_ivec2(), central_mass, Vec2::ZERO);
+                    }
+
+                    let extent = Vec2::splat(outer_radius);
+                    for location in cells_between(center - extent, center + extent) {
+                        let to_cell = location.as_vec2() - center;
+                        let radius = to_cell.length();
+                        if radius < inner_radius.max(f32::EPSILON) || radius > outer_radius {
+                            continue;
+                        }
+                        if raster.random_float(seed, location) >= density {
+                            continue;
+                        }
+
+                        // mass of the ring inside the orbit acts as if it was
+                        // concentrated at the center
+                        let ring_mass =
+                            density * mass * PI * (radius.powi(2) - inner_radius.powi(2));
+                        let speed =
+                            (params.gravity_constant * (central_mass + ring_mass) / radius).sqrt();
+                        let tangent = if clockwise {
+                            -to_cell.perp()
+                        } else {
+                            to_cell.perp()
+                        };
+
+                        raster.add_particle(location, mass, tangent / radius * speed);
+                    }
+                }
+                ScenarioObject::MassMap {
+                    ref path,
+                    position,
+                    mass,
+                    max_speed,
+                    ref image,
+                } => {
+                    let Some(image) = image else {
+                        warn!("mass map {} is not loaded, skipping", path.display());
+                        continue;
+                    };
+
+                    let origin = position.round().as_ivec2();
+                    for (location, mass, velocity) in image.particles(mass, max_speed) {
+                        raster.add_particle(origin + location, mass, velocity);
+                    }
+                }
+            }
+        }
+
+        if raster.dropped > 0 {
+            warn!(
+                "{} particles outside of the world are dropped",
+                raster.dropped
+            );
+        }
+
+        raster.cells
+    }
+}
+
+impl ScenarioObject {
+    /// Rejects values which would make invalid particles, e.g. velocities of
+    /// particles without mass are NaN.
+    fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            Self::Body { mass, .. } => check_mass(mass),
+            Self::RandomFill { density, mass, .. } => {
+                check_mass(mass)?;
+                check_density(density)
+            }
+            Self::Disk {
+                inner_radius,
+                outer_radius,
+                density,
+                mass,
+                central_mass,
+                ..
+            } => {
+                check_mass(mass)?;
+                check_density(density)?;
+                let radii = "radii must be 0 <= inner_radius <= outer_radius";
+                check_non_negative(inner_radius, radii)?;
+                check_non_negative(outer_radius - inner_radius, radii)?;
+                check_non_negative(central_mass, "central_mass must not be negative")
+            }
+            Self::MassMap {
+                mass, max_speed, ..
+            } => {
+                check_mass(mass)?;
+                check_non_negative(max_speed, "max_speed must not be negative")
+            }
+        }
+    }
+}
+
+fn check_mass(mass: f32) -> Result<(), &'static str> {
+    if mass > 0.0 && mass.is_finite() {
+        Ok(())
+    } else {
+        Err("mass must be positive")
+    }
+}
+
+fn check_non_negative(value: f32, reason: &'static str) -> Result<(), &'static str> {
+    if value >= 0.0 {
+        Ok(())
+    } else {
+        Err(reason)
+    }
+}
+
+fn check_density(density: f32) -> Result<(), &'static str> {
+    if (0.0..=1.0).contains(&density) {
+        Ok(())
+    } else {
+        Err("density must be between 0 and 1")
+    }
+}
+
+struct Raster {
+    size: (u32, u32),
+    boundary: BoundaryMode,
+    cells: Vec<CellData>,
+    /// Particles outside of a non-periodic world
+    dropped: usize,
+}
+
+impl Raster {
+    /// Same as `location_to_index` in `world_data.wgsl`
+    fn location_to_index(&self, location: IVec2) -> usize {
+        let width = self.size.0 as i32;
+        let height = self.size.1 as i32;
+        let looped = IVec2::new(location.x.rem_euclid(width), location.y.rem_euclid(height));
+        (looped.y * width + looped.x) as usize
+    }
+
+    /// Index of the cell, `None` outside of a non-periodic world.
+    fn cell_index(&self, location: IVec2) -> Option<usize> {
+        let inside = location.cmpge(IVec2::ZERO).all()
+            && location
+                .cmplt(IVec2::new(self.size.0 as i32, self.size.1 as i32))
+                .all();
+        (inside || self.boundary == BoundaryMode::Periodic)
+            .then(|| self.location_to_index(location))
+    }
+
+    fn random_float(&self, seed: u32, location: IVec2) -> f32 {
+        random_float_seeded(seed, self.location_to_index(location) as u32)
+    }
+
+    /// Adds particle to the cell, merging it with the existing one.
+    fn add_particle(&mut self, location: IVec2, mass: f32, velocity: Vec2) {
+        let Some(index) = self.cell_index(location) else {
+            self.dropped += 1;
+            return;
+        };
+        let cell = &mut self.cells[index];
+
+        if cell.particle_type == PARTICLE_NOTHING {
+            *cell = CellData::new_particle(mass, velocity);
+        } else {
+            cell.mass += mass;
+            cell.impulse += velocity * mass;
+        }
+    }
+}
+
+/// Locations of all cells with centers between `min` and `max`, inclusive.
+fn cells_between(min: Vec2, max: Vec2) -> impl Iterator<Item = IVec2> {
+    let min = min.ceil().as_ivec2();
+    let max = max.floor().as_ivec2();
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (16, 16);
+
+    fn particles(cells: &[CellData]) -> Vec<(usize, CellData)> {
+        cells
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, cell)| cell.particle_type != PARTICLE_NOTHING)
+            .collect()
+    }
+
+    #[test]
+    fn objects_are_parsed() {
+        let scenario = Scenario::from_ron(
+            "(objects: [
+                Body(position: (1.0, 2.0), mass: 3.0),
+                Disk(center: (8.0, 8.0), inner_radius: 0.0, outer_radius: 4.0, density: 0.5, mass: 1.0),
+            ])",
+        )
+        .unwrap();
+
+        assert_eq!(
+            scenario.objects,
+            [
+                ScenarioObject::Body {
+                    position: Vec2::new(1.0, 2.0),
+                    mass: 3.0,
+                    velocity: Vec2::ZERO,
+                },
+                ScenarioObject::Disk {
+                    center: Vec2::new(8.0, 8.0),
+                    inner_radius: 0.0,
+                    outer_radius: 4.0,
+                    density: 0.5,
+                    mass: 1.0,
+                    central_mass: 0.0,
+                    clockwise: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_objects_are_rejected() {
+        for (object, reason) in [
+            ("Body(position: (0.0, 0.0), mass: 0.0)", "mass"),
+            ("Body(position: (0.0, 0.0), mass: -1.0)", "mass"),
+            (
+                "RandomFill(min: (0.0, 0.0), max: (4.0, 4.0), density: 1.5, mass: 1.0)",
+                "density",
+            ),
+            (
+                "Disk(center: (0.0, 0.0), inner_radius: 4.0, outer_radius: 2.0, density: 0.5, mass: 1.0)",
+                "radii",
+            ),
+            (
+                "Disk(center: (0.0, 0.0), inner_radius: 0.0, outer_radius: 2.0, density: 0.5, mass: 1.0, central_mass: -1.0)",
+                "central_mass",
+            ),
+            ("MassMap(path: \"a.png\", mass: 1.0, max_speed: -1.0)", "max_speed"),
+        ] {
+            let source = format!("(objects: [Body(position: (0.0, 0.0), mass: 1.0), {object}])");
+            match Scenario::from_ron(&source) {
+                Err(ScenarioError::InvalidObject { index: 1, reason: err }) => {
+                    assert!(err.starts_with(reason), "{object}: {err}");
+                }
+                result => panic!("{object}: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn bodies_in_the_same_cell_merge() {
+        let scenario = Scenario {
+            objects: vec![
+                ScenarioObject::Body {
+                    position: Vec2::new(3.2, 4.0),
+                    mass: 1.0,
+                    velocity: Vec2::new(2.0, 0.0),
+                },
+                ScenarioObject::Body {
+                    position: Vec2::new(2.8, 4.3),
+                    mass: 3.0,
+                    velocity: Vec2::new(0.0, -1.0),
+                },
+            ],
+        };
+
+        let cells = scenario.rasterize(SIZE, default(), &SimulationParams::default());
+
+        let particles = particles(&cells);
+        assert_eq!(particles.len(), 1);
+        let (index, cell) = particles[0];
+        assert_eq!(index, 4 * 16 + 3);
+        assert_eq!(cell.mass, 4.0);
+        assert_eq!(cell.impulse, Vec2::new(2.0, -3.0));
+    }
+
+    #[test]
+    fn random_fill_is_deterministic_for_a_seed() {
+        let scenario = Scenario {
+            objects: vec![ScenarioObject::RandomFill {
+                min: Vec2::new(2.0, 2.0),
+                max: Vec2::new(13.0, 13.0),
+                density: 0.5,
+                mass: 2.0,
+            }],
+        };
+        let rasterize = |seed: u32| {
+            let params = SimulationParams { seed, ..default() };
+            scenario.rasterize(SIZE, default(), &params)
+        };
+
+        let cells = rasterize(7);
+        assert_eq!(cells, rasterize(7));
+        assert_ne!(cells, rasterize(8));
+
+        let particles = particles(&cells);
+        assert!((36..108).contains(&particles.len()), "{}", particles.len());
+        for (index, cell) in particles {
+            let (x, y) = (index % 16, index / 16);
+            assert!((2..=13).contains(&x) && (2..=13).contains(&y));
+            assert_eq!(cell, CellData::new_particle(2.0, Vec2::ZERO));
+        }
+    }
+
+    #[test]
+    fn particles_outside_of_the_world_wrap_only_if_periodic() {
+        let scenario = Scenario {
+            objects: vec![
+                ScenarioObject::Body {
+                    position: Vec2::new(-1.0, 4.0),
+                    mass: 1.0,
+                    velocity: Vec2::ZERO,
+                },
+                ScenarioObject::Body {
+                    position: Vec2::new(3.0, 16.0),
+                    mass: 2.0,
+                    velocity: Vec2::ZERO,
+                },
+            ],
+        };
+        let params = SimulationParams::default();
+
+        let cells = scenario.rasterize(SIZE, BoundaryMode::Periodic, &params);
+        let indices: Vec<_> = particles(&cells).iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [3, 4 * 16 + 15]);
+
+        for boundary in [BoundaryMode::Absorb, BoundaryMode::Reflect] {
+            let cells = scenario.rasterize(SIZE, boundary, &params);
+            assert!(particles(&cells).is_empty(), "{boundary:?}");
+        }
+    }
+
+    #[test]
+    fn png_is_loaded_as_mass_map() {
+        let path =
+            std::env::temp_dir().join(format!("voxel-physics-scenario-{}.png", std::process::id()));
+        let mut image = image::RgbaImage::new(3, 2);
+        image.put_pixel(2, 1, image::Rgba([255, 255, 255, 255]));
+        image.save(&path).unwrap();
+
+        let scenario = Scenario::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let cells = scenario
+            .unwrap()
+            .rasterize(SIZE, default(), &SimulationParams::default());
+        assert_eq!(
+            particles(&cells),
+            [(
+                16 + 2,
+                CellData::new_particle(DEFAULT_MASS_MAP_MASS, Vec2::ZERO)
+            )]
+        );
+    }
+}
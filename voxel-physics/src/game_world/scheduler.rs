@@ -0,0 +1,193 @@
+//! Order of the compute passes `GameWorldNode` dispatches every frame, kept
+//! apart from the dispatch itself so it can be tested without a GPU.
+
+/// Compute pass writing every cell of `data_next` from `data_prev`, the
+/// buffers are swapped after every phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Init,
+    PreUpdate,
+    UpdateGravity,
+    UpdateImpulse,
+    UpdatePosition,
+}
+
+impl Phase {
+    /// Phases of a single full simulation step, in order.
+    pub const STEP: [Self; 4] = [
+        Self::PreUpdate,
+        Self::UpdateGravity,
+        Self::UpdateImpulse,
+        Self::UpdatePosition,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchedulerState {
+    /// Waiting for the pipelines to compile
+    #[default]
+    Loading,
+    /// `init` pass is dispatched during the next frame
+    Init,
+    /// Simulation steps are dispatched every frame
+    Running,
+}
+
+/// Everything the scheduler depends on, gathered from the render world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameInput {
+    /// Whether all pipelines are compiled
+    pub pipelines_ready: bool,
+    /// Whether the world state is initialized, either by `init` pass or by an
+    /// upload. Reset to `false` to re-run `init`.
+    pub initialized: bool,
+    /// Full simulation steps to dispatch if the world is running
+    pub steps: u32,
+}
+
+/// Phases to dispatch during the frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramePlan {
+    pub phases: Vec<Phase>,
+    /// Full simulation steps in `phases`
+    pub steps: u32,
+    /// Whether the world state is initialized after `phases`
+    pub initialized: bool,
+    /// Whether the latest state can be drawn and read back, the pipelines are
+    /// compiled and the state is initialized
+    pub output: bool,
+}
+
+impl FramePlan {
+    /// Every phase writes into `data_next`, so after an odd number of phases
+    /// the latest state is in the other buffer.
+    pub fn swaps_buffers(&self) -> bool {
+        self.phases.len() % 2 == 1
+    }
+}
+
+/// Loading → init (once, skipped if the world was uploaded) → repeating
+/// [`Phase::STEP`] cycles, the world goes back to init when it is reset.
+#[derive(Clone, Debug, Default)]
+pub struct PhaseScheduler {
+    state: SchedulerState,
+}
+
+impl PhaseScheduler {
+    pub fn state(&self) -> SchedulerState {
+        self.state
+    }
+
+    /// Advances the state and returns the phases to dispatch during the frame.
+    pub fn plan_frame(&mut self, input: FrameInput) -> FramePlan {
+        self.state = match self.state {
+            SchedulerState::Loading if !input.pipelines_ready => SchedulerState::Loading,
+            SchedulerState::Loading | SchedulerState::Running if !input.initialized => {
+                SchedulerState::Init
+            }
+            SchedulerState::Loading => SchedulerState::Running,
+            state => state,
+        };
+
+        match self.state {
+            SchedulerState::Loading => FramePlan {
+                initialized: input.initialized,
+                ..Default::default()
+            },
+            SchedulerState::Init => {
+                self.state = SchedulerState::Running;
+                FramePlan {
+                    phases: vec![Phase::Init],
+                    steps: 0,
+                    initialized: true,
+                    output: true,
+                }
+            }
+            SchedulerState::Running => FramePlan {
+                phases: (0..input.steps).flat_map(|_| Phase::STEP).collect(),
+                steps: input.steps,
+                initialized: true,
+                output: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(pipelines_ready: bool, initialized: bool, steps: u32) -> FrameInput {
+        FrameInput {
+            pipelines_ready,
+            initialized,
+            steps,
+        }
+    }
+
+    #[test]
+    fn nothing_is_dispatched_while_loading() {
+        let mut scheduler = PhaseScheduler::default();
+
+        // an uploaded world must not be drawn before the pipelines compile
+        for initialized in [false, true] {
+            let plan = scheduler.plan_frame(input(false, initialized, 3));
+
+            assert_eq!(scheduler.state(), SchedulerState::Loading);
+            assert!(plan.phases.is_empty());
+            assert_eq!(plan.steps, 0);
+            assert_eq!(plan.initialized, initialized);
+            assert!(!plan.output);
+        }
+    }
+
+    #[test]
+    fn init_runs_once_before_steps() {
+        let mut scheduler = PhaseScheduler::default();
+
+        let plan = scheduler.plan_frame(input(true, false, 3));
+        assert_eq!(plan.phases, [Phase::Init]);
+        assert_eq!(plan.steps, 0);
+        assert!(plan.initialized && plan.output);
+        assert!(plan.swaps_buffers());
+
+        let plan = scheduler.plan_frame(input(true, true, 2));
+        assert_eq!(plan.phases, [Phase::STEP, Phase::STEP].concat());
+        assert_eq!(plan.steps, 2);
+        assert!(!plan.swaps_buffers());
+        assert_eq!(scheduler.state(), SchedulerState::Running);
+    }
+
+    #[test]
+    fn uploaded_world_skips_init() {
+        let mut scheduler = PhaseScheduler::default();
+
+        let plan = scheduler.plan_frame(input(true, true, 1));
+
+        assert_eq!(plan.phases, Phase::STEP);
+        assert_eq!(scheduler.state(), SchedulerState::Running);
+    }
+
+    #[test]
+    fn paused_world_is_still_drawn() {
+        let mut scheduler = PhaseScheduler::default();
+        scheduler.plan_frame(input(true, false, 0));
+
+        let plan = scheduler.plan_frame(input(true, true, 0));
+
+        assert!(plan.phases.is_empty());
+        assert!(plan.output);
+    }
+
+    #[test]
+    fn reset_reruns_init() {
+        let mut scheduler = PhaseScheduler::default();
+        scheduler.plan_frame(input(true, false, 1));
+        scheduler.plan_frame(input(true, true, 1));
+
+        let plan = scheduler.plan_frame(input(true, false, 1));
+
+        assert_eq!(plan.phases, [Phase::Init]);
+        assert_eq!(scheduler.state(), SchedulerState::Running);
+    }
+}
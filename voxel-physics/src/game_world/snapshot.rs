@@ -0,0 +1,278 @@
+//! Versioned binary format for persisting the world state.
+//!
+//! All header fields are little-endian and the cells follow the header as a raw
+//! array of [`CellData`], exactly as they are laid out in the storage buffers:
+//!
+//! | Field   | Type       | Description                              |
+//! |---------|------------|------------------------------------------|
+//! | magic   | `[u8; 8]`  | [`SNAPSHOT_MAGIC`]                       |
+//! | version | `u32`      | [`SNAPSHOT_VERSION`]                     |
+//! | width   | `u32`      | World width in cells                     |
+//! | height  | `u32`      | World height in cells                    |
+//! | step    | `u64`      | Number of simulation steps done          |
+//! | stride  | `u32`      | Size of a single [`CellData`] in bytes   |
+//! | cells   | `[u8]`     | `width * height * stride` bytes of cells |
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    mem::size_of,
+    path::Path,
+};
+
+use crate::game_world::CellData;
+
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"VXPHYSWS";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    StrideMismatch {
+        expected: u32,
+        found: u32,
+    },
+    SizeMismatch {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::InvalidMagic => write!(f, "not a world snapshot"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            Self::StrideMismatch { expected, found } => {
+                write!(
+                    f,
+                    "cell stride mismatch: expected {expected}, found {found}"
+                )
+            }
+            Self::SizeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "world size mismatch: expected {expected:?}, found {found:?}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// State of the whole world at some simulation step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldSnapshot {
+    pub size: (u32, u32),
+    pub step: u64,
+    pub cells: Vec<CellData>,
+}
+
+impl WorldSnapshot {
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.size.0.to_le_bytes())?;
+        writer.write_all(&self.size.1.to_le_bytes())?;
+        writer.write_all(&self.step.to_le_bytes())?;
+        writer.write_all(&(size_of::<CellData>() as u32).to_le_bytes())?;
+        writer.write_all(bytemuck::cast_slice(&self.cells))?;
+
+        Ok(())
+    }
+
+    /// Reads a snapshot of a world of `world_size`, the header is checked
+    /// before the cells are allocated.
+    pub fn read(mut reader: impl Read, world_size: (u32, u32)) -> Result<Self, SnapshotError> {
+        let mut magic = [0; SNAPSHOT_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let step = read_u64(&mut reader)?;
+
+        let stride = read_u32(&mut reader)?;
+        let expected_stride = size_of::<CellData>() as u32;
+        if stride != expected_stride {
+            return Err(SnapshotError::StrideMismatch {
+                expected: expected_stride,
+                found: stride,
+            });
+        }
+
+        if (width, height) != world_size {
+            return Err(SnapshotError::SizeMismatch {
+                expected: world_size,
+                found: (width, height),
+            });
+        }
+
+        let cells_size = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|count| count.checked_mul(stride as usize))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "world is too large"))?;
+        let mut cells = vec![0; cells_size];
+        reader.read_exact(&mut cells)?;
+
+        Ok(Self {
+            size: (width, height),
+            step,
+            cells: bytemuck::pod_collect_to_vec(&cells),
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>, world_size: (u32, u32)) -> Result<Self, SnapshotError> {
+        Self::read(BufReader::new(File::open(path)?), world_size)
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    const SIZE: (u32, u32) = (3, 2);
+
+    fn snapshot() -> WorldSnapshot {
+        let mut cells = vec![CellData::new_empty(); 6];
+        cells[1] = CellData::new_particle(2.0, Vec2::new(0.5, -1.0));
+        cells[4] = CellData::new_particle(3.0, Vec2::ZERO);
+
+        WorldSnapshot {
+            size: SIZE,
+            step: 42,
+            cells,
+        }
+    }
+
+    fn bytes(snapshot: &WorldSnapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Offset of the header field, see the table above
+    fn field_offset(field: &str) -> usize {
+        match field {
+            "version" => 8,
+            "width" => 12,
+            "height" => 16,
+            "step" => 20,
+            "stride" => 28,
+            _ => unreachable!(),
+        }
+    }
+
+    fn with_u32(mut bytes: Vec<u8>, field: &str, value: u32) -> Vec<u8> {
+        let offset = field_offset(field);
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let snapshot = snapshot();
+        let bytes = bytes(&snapshot);
+
+        assert_eq!(bytes.len(), 32 + 6 * size_of::<CellData>());
+        assert_eq!(
+            WorldSnapshot::read(bytes.as_slice(), SIZE).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn truncated_snapshots_are_rejected() {
+        let bytes = bytes(&snapshot());
+
+        // inside the header and inside the cells
+        for len in [0, 4, 10, 30, bytes.len() - 1] {
+            let result = WorldSnapshot::read(&bytes[..len], SIZE);
+            assert!(
+                matches!(result, Err(SnapshotError::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof),
+                "{len}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_headers_are_rejected() {
+        let bytes = bytes(&snapshot());
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(matches!(
+            WorldSnapshot::read(magic.as_slice(), SIZE),
+            Err(SnapshotError::InvalidMagic)
+        ));
+
+        let version = with_u32(bytes.clone(), "version", 7);
+        assert!(matches!(
+            WorldSnapshot::read(version.as_slice(), SIZE),
+            Err(SnapshotError::UnsupportedVersion(7))
+        ));
+
+        let stride = with_u32(bytes.clone(), "stride", 4);
+        assert!(matches!(
+            WorldSnapshot::read(stride.as_slice(), SIZE),
+            Err(SnapshotError::StrideMismatch { found: 4, .. })
+        ));
+
+        // rejected before the cells are allocated
+        let huge = with_u32(with_u32(bytes, "width", u32::MAX), "height", u32::MAX);
+        assert!(matches!(
+            WorldSnapshot::read(huge.as_slice(), SIZE),
+            Err(SnapshotError::SizeMismatch {
+                expected: SIZE,
+                found: (u32::MAX, u32::MAX),
+            })
+        ));
+    }
+}
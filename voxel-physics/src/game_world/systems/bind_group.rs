@@ -6,8 +6,11 @@ use bevy::{
     },
 };
 
-use crate::game_world::{GameWorldBindGroup, GameWorldData, GameWorldPipeline};
+use crate::game_world::{
+    GameWorldBindGroups, GameWorldData, GameWorldPipeline, SimulationParams, VisualizationSettings,
+};
 
+#[allow(clippy::too_many_arguments)]
 pub fn prepare_bind_group_sys(
     mut commands: Commands,
     pipeline: Res<GameWorldPipeline>,
@@ -15,17 +18,25 @@ pub fn prepare_bind_group_sys(
     mut game_world_data: ResMut<GameWorldData>,
     render_device: Res<RenderDevice>,
     fallback_image: Res<FallbackImage>,
+    params: Res<SimulationParams>,
+    visualization: Res<VisualizationSettings>,
 ) {
-    game_world_data.swap();
+    game_world_data.params = *params;
+    game_world_data.visualization = (*visualization).into();
+
+    let mut swapped_data = game_world_data.clone();
+    swapped_data.swap();
 
-    let prepared = game_world_data
-        .as_bind_group(
+    let [bind_group, swapped_bind_group] = [&*game_world_data, &swapped_data].map(|data| {
+        data.as_bind_group(
             &pipeline.world_bind_group_layout,
             &render_device,
             &gpu_images,
             &fallback_image,
         )
-        .unwrap();
+        .unwrap()
+        .bind_group
+    });
 
-    commands.insert_resource(GameWorldBindGroup(prepared.bind_group));
+    commands.insert_resource(GameWorldBindGroups([bind_group, swapped_bind_group]));
 }
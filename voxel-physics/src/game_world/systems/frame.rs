@@ -0,0 +1,166 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+use crate::{
+    game_world::{
+        frame::WorldFrame, FrameReadbacks, FrameReceiver, FrameRecorder, FrameRecording,
+        FrameRequests, FrameSender, GameWorldData, GameWorldSize, SaveWorldFrame,
+        SimulationProgress, WorldFileFailed, WorldFrameSaved,
+    },
+    utils::readback::{copy_texture_to_staging, unpad_rows},
+};
+
+pub fn frame_control_sys(
+    input: Res<Input<KeyCode>>,
+    progress: Res<SimulationProgress>,
+    mut recording: ResMut<FrameRecording>,
+    mut save_events: EventWriter<SaveWorldFrame>,
+) {
+    if input.just_pressed(KeyCode::F2) {
+        save_events.send(SaveWorldFrame(
+            format!("screenshot-{}.png", progress.step).into(),
+        ));
+    }
+
+    if input.just_pressed(KeyCode::F3) {
+        recording.directory = match recording.directory {
+            Some(_) => None,
+            None => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                Some(PathBuf::from(format!("recording-{timestamp}")))
+            }
+        };
+
+        match &recording.directory {
+            Some(directory) => info!("recording frames into {}", directory.display()),
+            None => info!("recording stopped"),
+        }
+    }
+}
+
+pub fn save_world_frame_sys(
+    mut events: EventReader<SaveWorldFrame>,
+    mut requests: ResMut<FrameRequests>,
+) {
+    requests
+        .0
+        .extend(events.read().map(|SaveWorldFrame(path)| path.clone()));
+}
+
+/// Copies the world texture for requested and recorded frames into a free
+/// [`FrameReadback`], requested frames wait until one is free and the texture
+/// is prepared. The texture is drawn by `GameWorldNode` at the end of the
+/// previous frame, so it matches [`SimulationProgress`] updated by it.
+///
+/// [`FrameReadback`]: crate::game_world::FrameReadback
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_frames_sys(
+    game_world_data: Res<GameWorldData>,
+    gpu_images: Res<RenderAssets<Image>>,
+    mut frame_requests: ResMut<FrameRequests>,
+    mut readbacks: ResMut<FrameReadbacks>,
+    mut recorder: ResMut<FrameRecorder>,
+    recording: Res<FrameRecording>,
+    progress: Res<SimulationProgress>,
+    world_size: Res<GameWorldSize>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    readbacks.pending.append(&mut frame_requests.0);
+
+    // recorded frames are taken later, once a readback is free
+    if !readbacks.has_free() {
+        return;
+    }
+    let Some(gpu_image) = gpu_images.get(&game_world_data.image) else {
+        return;
+    };
+
+    let mut paths = std::mem::take(&mut readbacks.pending);
+    if progress.initialized {
+        paths.extend(recorder.next_frame(&recording, progress.step));
+    }
+
+    if paths.is_empty() {
+        return;
+    }
+
+    let frame = readbacks
+        .reserve(&render_device, *world_size, progress.step, paths)
+        .unwrap();
+    copy_texture_to_staging(
+        &render_device,
+        &render_queue,
+        &gpu_image.texture,
+        world_size.0,
+        &frame.readback.staging,
+    );
+}
+
+/// Maps the frames copied by [`prepare_frames_sys`] and sends them to the
+/// main world once they are available, never blocks. Frames which fail to
+/// map are sent as errors.
+pub fn read_frames_sys(
+    mut readbacks: ResMut<FrameReadbacks>,
+    frame_sender: Res<FrameSender>,
+    world_size: Res<GameWorldSize>,
+    render_device: Res<RenderDevice>,
+) {
+    for frame in &mut readbacks.readbacks {
+        let Some(result) = frame.readback.try_read(&render_device) else {
+            continue;
+        };
+
+        let world_frame = result
+            .map(|data| WorldFrame {
+                size: world_size.0,
+                step: frame.step,
+                pixels: unpad_rows(&data, world_size.width()),
+            })
+            .map_err(|err| format!("failed to map frame buffer at step {}: {err}", frame.step));
+
+        for path in frame.paths.drain(..) {
+            // the main world is gone while the app shuts down
+            let _ = frame_sender.0.send((path, world_frame.clone()));
+        }
+    }
+}
+
+/// Writes frames read back in the render world, sends [`WorldFileFailed`] for
+/// every frame which couldn't be read back or written.
+pub fn write_world_frame_sys(
+    receiver: Res<FrameReceiver>,
+    mut saved_events: EventWriter<WorldFrameSaved>,
+    mut failed_events: EventWriter<WorldFileFailed>,
+) {
+    for (path, frame) in receiver.0.try_iter() {
+        let result = frame.and_then(|frame| {
+            frame.save(&path).map_err(|err| err.to_string())?;
+            Ok(frame.step)
+        });
+
+        match result {
+            Ok(step) => {
+                debug!("saved frame {} at step {step}", path.display());
+                saved_events.send(WorldFrameSaved { path, step });
+            }
+            Err(message) => {
+                error!("failed to save frame {}: {message}", path.display());
+                failed_events.send(WorldFileFailed { path, message });
+            }
+        }
+    }
+}
@@ -2,15 +2,20 @@ use bevy::{
     prelude::*,
     render::{
         render_resource::{
-            BufferInitDescriptor, BufferUsages, Extent3d, TextureDimension, TextureFormat,
-            TextureUsages,
+            BufferDescriptor, BufferInitDescriptor, BufferUsages, Extent3d, TextureDimension,
+            TextureFormat, TextureUsages,
         },
         renderer::RenderDevice,
     },
 };
 
 use crate::{
-    game_world::{CellData, GameWorldData, WorldSprite, WORLD_SIZE},
+    game_world::{
+        fft_kernel_spectrum, get_fft_data_size, get_fft_kernel_data_size, get_lost_mass_data_size,
+        get_pyramid_data_size, snapshot::WorldSnapshot, BoundaryMode, CellData, GameWorldData,
+        GameWorldSize, GameWorldUploads, GravityMode, LoadedScenario, ReinitializeWorld,
+        SimulationParams, StatsPartial, VisualizationParams, WorldSprite,
+    },
     utils::image::ImageUtils,
 };
 
@@ -18,13 +23,13 @@ pub fn world_init_sys(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
     render_device: Res<RenderDevice>,
+    world_size: Res<GameWorldSize>,
+    gravity: Res<GravityMode>,
 ) {
-    commands.spawn(Camera2dBundle::default());
-
     let image = Image::new_fill(
         Extent3d {
-            width: WORLD_SIZE.0,
-            height: WORLD_SIZE.1,
+            width: world_size.width(),
+            height: world_size.height(),
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
@@ -32,39 +37,135 @@ pub fn world_init_sys(
         TextureFormat::Rgba8Unorm,
     )
     .with_description_usage(
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+        TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC
+            | TextureUsages::STORAGE_BINDING
+            | TextureUsages::TEXTURE_BINDING,
     );
     let image = images.add(image);
 
-    commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(WORLD_SIZE.0 as f32, WORLD_SIZE.1 as f32)),
-                ..default()
-            },
-            texture: image.clone(),
-            ..default()
-        })
-        .insert(WorldSprite);
-
-    let data = vec![CellData::default(); (WORLD_SIZE.0 * WORLD_SIZE.1) as usize];
+    let data = vec![CellData::default(); world_size.cells_count()];
     let data = bytemuck::cast_slice(&data);
 
     let data_prev = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: None,
         contents: data,
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
     });
 
     let data_next = render_device.create_buffer_with_data(&BufferInitDescriptor {
         label: None,
         contents: data,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+    });
+
+    let stats = render_device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: StatsPartial::get_stats_data_size(*world_size),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    // zeroed by `init` pass or on upload
+    let lost_mass = render_device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: get_lost_mass_data_size(*world_size),
         usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    // written by `build_pyramid` before it is read
+    let pyramid = render_device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: get_pyramid_data_size(*world_size, *gravity),
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
     });
 
+    // written by the FFT passes before it is read
+    let fft = render_device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: get_fft_data_size(*world_size, *gravity),
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+
+    let fft_kernel = match *gravity {
+        GravityMode::Fft => render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&fft_kernel_spectrum(*world_size)),
+            usage: BufferUsages::STORAGE,
+        }),
+        GravityMode::Relay | GravityMode::Pyramid => {
+            render_device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: get_fft_kernel_data_size(*world_size, *gravity),
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        }
+    };
+
     commands.insert_resource(GameWorldData {
         image,
         data_prev,
         data_next,
+        stats,
+        lost_mass,
+        pyramid,
+        fft,
+        fft_kernel,
+        params: SimulationParams::default(),
+        visualization: VisualizationParams::default(),
     });
 }
+
+/// Spawns camera and sprite displaying the world texture, must run after
+/// [`world_init_sys`].
+pub fn world_view_init_sys(
+    mut commands: Commands,
+    game_world_data: Res<GameWorldData>,
+    world_size: Res<GameWorldSize>,
+) {
+    commands.spawn(Camera2dBundle::default());
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(
+                    world_size.width() as f32,
+                    world_size.height() as f32,
+                )),
+                ..default()
+            },
+            texture: game_world_data.image.clone(),
+            ..default()
+        })
+        .insert(WorldSprite);
+}
+
+pub fn reinitialize_world_sys(
+    mut events: EventReader<ReinitializeWorld>,
+    mut params: ResMut<SimulationParams>,
+    mut uploads: ResMut<GameWorldUploads>,
+    loaded_scenario: Res<LoadedScenario>,
+    world_size: Res<GameWorldSize>,
+    boundary: Res<BoundaryMode>,
+) {
+    for event in events.read() {
+        if let Some(seed) = event.seed {
+            params.seed = seed;
+        }
+
+        match &loaded_scenario.0 {
+            Some(scenario) => {
+                uploads.snapshot = Some(WorldSnapshot {
+                    size: world_size.0,
+                    step: 0,
+                    cells: scenario.rasterize(world_size.0, *boundary, &params),
+                });
+            }
+            None => uploads.reinitialize = true,
+        }
+    }
+}
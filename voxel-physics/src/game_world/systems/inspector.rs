@@ -0,0 +1,152 @@
+use bevy::{prelude::*, render::renderer::RenderDevice, window::PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+
+use crate::game_world::{
+    cursor_to_cell, CellData, CellInspector, GameWorldSize, GameWorldViewportScale,
+    InspectedRegion, InspectorReadback, InspectorReceiver, InspectorSender, WorldSprite,
+    INSPECTOR_RADIUS, PARTICLE_NOTHING, PARTICLE_REGULAR,
+};
+
+/// Inspects the cell under the cursor, nothing is inspected while the cursor
+/// is outside of the world.
+pub fn inspect_cell_sys(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    sprite_q: Query<&Transform, With<WorldSprite>>,
+    scale: Res<GameWorldViewportScale>,
+    world_size: Res<GameWorldSize>,
+    mut inspector: ResMut<CellInspector>,
+) {
+    let location = window_q.get_single().ok().and_then(|window| {
+        let (camera, camera_transform) = camera_q.single();
+        cursor_to_cell(
+            window,
+            camera,
+            camera_transform,
+            sprite_q.single(),
+            &scale,
+            &world_size,
+        )
+    });
+    let location = location.filter(|location| {
+        location.cmpge(IVec2::ZERO).all()
+            && location.x < world_size.width() as i32
+            && location.y < world_size.height() as i32
+    });
+
+    // avoid triggering change detection every frame
+    inspector.set_if_neq(CellInspector { location });
+}
+
+/// Shows [`CellData`] of the inspected cell and masses of the cells around it.
+pub fn cell_inspector_ui_sys(
+    mut contexts: EguiContexts,
+    inspector: Res<CellInspector>,
+    region: Res<InspectedRegion>,
+) {
+    if inspector.location.is_none() {
+        return;
+    }
+    let Some(cell) = region.cell(IVec2::ZERO) else {
+        return;
+    };
+
+    egui::Window::new("Cell")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("cell_data")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    let mut row = |name: &str, value: String| {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    };
+
+                    row(
+                        "location",
+                        format!("{}, {}", region.center.x, region.center.y),
+                    );
+                    row("step", region.step.to_string());
+                    row("type", particle_type_name(cell).into());
+                    row("mass", format!("{:.4}", cell.mass));
+                    row("impulse", format_vec(cell.impulse));
+                    row("relative_pos", format_vec(cell.relative_pos));
+                    row("to_gravity_source", format_vec(cell.to_gravity_source));
+                    row("gravity_strength", format!("{:.4}", cell.gravity_strength));
+                });
+
+            ui.separator();
+            ui.label("Mass around");
+            egui::Grid::new("region_mass").show(ui, |ui| {
+                for y in -INSPECTOR_RADIUS..=INSPECTOR_RADIUS {
+                    for x in -INSPECTOR_RADIUS..=INSPECTOR_RADIUS {
+                        let mass = match region.cell(IVec2::new(x, y)) {
+                            Some(cell) if cell.particle_type != PARTICLE_NOTHING => {
+                                format!("{:.2}", cell.mass)
+                            }
+                            Some(_) => "·".into(),
+                            None => "×".into(),
+                        };
+                        ui.monospace(mass);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn particle_type_name(cell: &CellData) -> &'static str {
+    match cell.particle_type {
+        PARTICLE_NOTHING => "nothing",
+        PARTICLE_REGULAR => "regular",
+        _ => "unknown",
+    }
+}
+
+fn format_vec(value: Vec2) -> String {
+    format!("{:.4}, {:.4}", value.x, value.y)
+}
+
+/// Maps the cells copied by `GameWorldNode` and sends them to the main world
+/// once they are available, never blocks.
+pub fn read_inspector_sys(
+    mut readback: ResMut<InspectorReadback>,
+    sender: Res<InspectorSender>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(result) = readback.readback.try_read(&render_device) else {
+        return;
+    };
+
+    match result {
+        Ok(data) => {
+            let cells: Vec<CellData> = bytemuck::pod_collect_to_vec(&data);
+            let region = InspectedRegion {
+                cells: readback
+                    .cell_indices
+                    .iter()
+                    .zip(cells)
+                    .map(|(index, cell)| index.map(|_| cell))
+                    .collect(),
+                ..readback.region.clone()
+            };
+            // the main world is gone while the app shuts down
+            let _ = sender.0.send(region);
+        }
+        Err(err) => {
+            error!("failed to map inspector buffer: {err}");
+        }
+    }
+}
+
+/// Updates [`InspectedRegion`] with the latest one sent from the render world.
+pub fn receive_inspector_sys(
+    receiver: Res<InspectorReceiver>,
+    mut region: ResMut<InspectedRegion>,
+) {
+    if let Some(latest) = receiver.0.try_iter().last() {
+        *region = latest;
+    }
+}
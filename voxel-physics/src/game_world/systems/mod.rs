@@ -1,7 +1,27 @@
 pub use bind_group::*;
 pub use control::*;
+pub use frame::*;
 pub use init::*;
+pub use inspector::*;
+pub use paint::*;
+pub use pipeline_errors::*;
+pub use scenario::*;
+pub use simulation_control::*;
+pub use snapshot::*;
+pub use stats::*;
+pub use transfer::*;
+pub use visualization::*;
 
 mod bind_group;
 mod control;
+mod frame;
 mod init;
+mod inspector;
+mod paint;
+mod pipeline_errors;
+mod scenario;
+mod simulation_control;
+mod snapshot;
+mod stats;
+mod transfer;
+mod visualization;
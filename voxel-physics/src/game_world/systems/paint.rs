@@ -0,0 +1,88 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::game_world::{
+    BrushSettings, CellData, GameWorldSize, GameWorldUploads, GameWorldViewportScale, WorldSprite,
+};
+
+/// Converts cursor position into the location of the cell under it.
+///
+/// The cell may be out of the world bounds.
+pub fn cursor_to_cell(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    sprite_transform: &Transform,
+    scale: &GameWorldViewportScale,
+    world_size: &GameWorldSize,
+) -> Option<IVec2> {
+    let cursor = window.cursor_position()?;
+    let world_pos = camera.viewport_to_world_2d(camera_transform, cursor)?;
+
+    // sprite is scaled by `1.0 / scale` around its center
+    let local_pos = (world_pos - sprite_transform.translation.truncate()) * scale.0;
+
+    // texture rows go from top to bottom
+    let cell = Vec2::new(
+        local_pos.x + world_size.width() as f32 / 2.0,
+        world_size.height() as f32 / 2.0 - local_pos.y,
+    );
+
+    Some(cell.floor().as_ivec2())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn world_paint_sys(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    sprite_q: Query<&Transform, With<WorldSprite>>,
+    mouse: Res<Input<MouseButton>>,
+    scale: Res<GameWorldViewportScale>,
+    world_size: Res<GameWorldSize>,
+    brush: Res<BrushSettings>,
+    mut uploads: ResMut<GameWorldUploads>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+    let sprite_transform = sprite_q.single();
+
+    let Some(center) = cursor_to_cell(
+        window,
+        camera,
+        camera_transform,
+        sprite_transform,
+        &scale,
+        &world_size,
+    ) else {
+        return;
+    };
+
+    let cell = CellData::new_particle(brush.mass, brush.velocity);
+    let radius = brush.radius.max(0.0);
+    let radius_cells = radius.ceil() as i32;
+
+    for y in -radius_cells..=radius_cells {
+        for x in -radius_cells..=radius_cells {
+            let offset = IVec2::new(x, y);
+            if offset.as_vec2().length() > radius {
+                continue;
+            }
+
+            let location = center + offset;
+            if location.x < 0
+                || location.y < 0
+                || location.x >= world_size.width() as i32
+                || location.y >= world_size.height() as i32
+            {
+                continue;
+            }
+
+            uploads.cells.push((location.as_uvec2(), cell));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::{prelude::*, render::render_resource::PipelineCache};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+
+use crate::game_world::{
+    GameWorldPipeline, PipelineCompilationFailed, PipelineErrors, PipelineErrorsReceiver,
+    PipelineErrorsSender,
+};
+
+/// Sends the pipeline errors to the main world whenever they change, e.g. when
+/// a hot reloaded shader fixes them.
+pub fn check_pipelines_sys(
+    pipeline: Res<GameWorldPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    sender: Res<PipelineErrorsSender>,
+    mut last_errors: Local<PipelineErrors>,
+) {
+    let errors = PipelineErrors(pipeline.errors(&pipeline_cache));
+    if errors != *last_errors {
+        // the main world is gone while the app shuts down
+        let _ = sender.0.send(errors.clone());
+        *last_errors = errors;
+    }
+}
+
+/// Updates [`PipelineErrors`] and logs and sends [`PipelineCompilationFailed`]
+/// for every new error.
+pub fn receive_pipeline_errors_sys(
+    receiver: Res<PipelineErrorsReceiver>,
+    mut errors: ResMut<PipelineErrors>,
+    mut failed_events: EventWriter<PipelineCompilationFailed>,
+) {
+    let Some(latest) = receiver.0.try_iter().last() else {
+        return;
+    };
+
+    for error in &latest.0 {
+        if errors.0.contains(error) {
+            continue;
+        }
+
+        error!(
+            "failed to compile `{}` pipeline: {}",
+            error.entry_point, error.message
+        );
+        failed_events.send(error.clone());
+    }
+
+    if !errors.0.is_empty() && latest.0.is_empty() {
+        info!("pipeline errors are fixed");
+    }
+
+    *errors = latest;
+}
+
+/// Shows the pipeline errors on top of the world.
+pub fn pipeline_errors_ui_sys(mut contexts: EguiContexts, errors: Res<PipelineErrors>) {
+    if errors.0.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Shader errors")
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for error in &errors.0 {
+                ui.colored_label(
+                    egui::Color32::LIGHT_RED,
+                    format!("`{}`: {}", error.entry_point, error.message),
+                );
+            }
+        });
+}
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+use crate::game_world::{
+    scenario::Scenario, snapshot::WorldSnapshot, BoundaryMode, GameWorldSize, GameWorldUploads,
+    LoadScenario, LoadedScenario, SimulationParams, WorldFileFailed,
+};
+
+pub fn load_scenario_sys(
+    mut events: EventReader<LoadScenario>,
+    mut uploads: ResMut<GameWorldUploads>,
+    mut loaded_scenario: ResMut<LoadedScenario>,
+    world_size: Res<GameWorldSize>,
+    boundary: Res<BoundaryMode>,
+    params: Res<SimulationParams>,
+    mut failed_events: EventWriter<WorldFileFailed>,
+) {
+    for LoadScenario(path) in events.read() {
+        match Scenario::load(path) {
+            Ok(scenario) => {
+                info!("loaded scenario {}", path.display());
+                uploads.snapshot = Some(WorldSnapshot {
+                    size: world_size.0,
+                    step: 0,
+                    cells: scenario.rasterize(world_size.0, *boundary, &params),
+                });
+                loaded_scenario.0 = Some(scenario);
+            }
+            Err(err) => {
+                error!("failed to load scenario {}: {err}", path.display());
+                failed_events.send(WorldFileFailed {
+                    path: path.clone(),
+                    message: err.to_string(),
+                });
+            }
+        }
+    }
+}
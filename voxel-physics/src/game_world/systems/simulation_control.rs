@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+
+use crate::game_world::{
+    ReinitializeWorld, SimulationClock, SimulationControl, MAX_STEPS_PER_FRAME, MAX_TICK_RATE,
+    MIN_TICK_RATE,
+};
+
+pub fn simulation_control_sys(
+    input: Res<Input<KeyCode>>,
+    mut control: ResMut<SimulationControl>,
+    mut reinitialize_events: EventWriter<ReinitializeWorld>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        control.paused = !control.paused;
+    }
+    if input.just_pressed(KeyCode::Period) {
+        control.paused = true;
+        control.single_step = true;
+    }
+    if input.just_pressed(KeyCode::Equals) {
+        match &mut control.tick_rate {
+            Some(tick_rate) => *tick_rate = (*tick_rate * 2.0).min(MAX_TICK_RATE),
+            None => {
+                control.steps_per_frame = (control.steps_per_frame + 1).min(MAX_STEPS_PER_FRAME);
+            }
+        }
+    }
+    if input.just_pressed(KeyCode::Minus) {
+        match &mut control.tick_rate {
+            Some(tick_rate) => *tick_rate = (*tick_rate / 2.0).max(MIN_TICK_RATE),
+            None => {
+                control.steps_per_frame = control.steps_per_frame.saturating_sub(1).max(1);
+            }
+        }
+    }
+    if input.just_pressed(KeyCode::R) {
+        let seed = input
+            .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+            .then(rand::random);
+        reinitialize_events.send(ReinitializeWorld { seed });
+    }
+}
+
+/// Resets single step request after it was extracted into the render world.
+pub fn reset_single_step_sys(mut control: ResMut<SimulationControl>) {
+    if control.single_step {
+        control.single_step = false;
+    }
+}
+
+/// Schedules steps for the current frame right before they are extracted into
+/// the render world.
+pub fn tick_simulation_clock_sys(
+    control: Res<SimulationControl>,
+    mut clock: ResMut<SimulationClock>,
+    time: Res<Time>,
+) {
+    clock.tick(&control, time.delta());
+}
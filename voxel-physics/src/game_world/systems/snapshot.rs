@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use crate::game_world::{
+    snapshot::WorldSnapshot, GameWorldSize, GameWorldUploads, LoadWorldSnapshot, SaveWorldSnapshot,
+    SnapshotReceiver, SnapshotRequests, WorldFileFailed, WorldSnapshotSaved, QUICK_SNAPSHOT_PATH,
+};
+
+pub fn world_snapshot_control_sys(
+    input: Res<Input<KeyCode>>,
+    mut save_events: EventWriter<SaveWorldSnapshot>,
+    mut load_events: EventWriter<LoadWorldSnapshot>,
+) {
+    if input.just_pressed(KeyCode::F5) {
+        save_events.send(SaveWorldSnapshot(QUICK_SNAPSHOT_PATH.into()));
+    }
+    if input.just_pressed(KeyCode::F9) {
+        load_events.send(LoadWorldSnapshot(QUICK_SNAPSHOT_PATH.into()));
+    }
+}
+
+pub fn save_world_snapshot_sys(
+    mut events: EventReader<SaveWorldSnapshot>,
+    mut requests: ResMut<SnapshotRequests>,
+) {
+    requests
+        .0
+        .extend(events.read().map(|SaveWorldSnapshot(path)| path.clone()));
+}
+
+pub fn load_world_snapshot_sys(
+    mut events: EventReader<LoadWorldSnapshot>,
+    mut uploads: ResMut<GameWorldUploads>,
+    world_size: Res<GameWorldSize>,
+) {
+    for LoadWorldSnapshot(path) in events.read() {
+        match WorldSnapshot::load(path, world_size.0) {
+            Ok(snapshot) => {
+                info!(
+                    "loaded snapshot {} at step {}",
+                    path.display(),
+                    snapshot.step
+                );
+                uploads.snapshot = Some(snapshot);
+            }
+            Err(err) => {
+                error!("failed to load snapshot {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Writes snapshots read back in the render world, sends
+/// [`WorldFileFailed`] for every snapshot which couldn't be read back or
+/// written.
+pub fn write_world_snapshot_sys(
+    receiver: Res<SnapshotReceiver>,
+    mut saved_events: EventWriter<WorldSnapshotSaved>,
+    mut failed_events: EventWriter<WorldFileFailed>,
+) {
+    for (path, snapshot) in receiver.0.try_iter() {
+        let result = snapshot.and_then(|snapshot| {
+            snapshot.save(&path).map_err(|err| err.to_string())?;
+            Ok(snapshot.step)
+        });
+
+        match result {
+            Ok(step) => {
+                info!("saved snapshot {} at step {step}", path.display());
+                saved_events.send(WorldSnapshotSaved { path, step });
+            }
+            Err(message) => {
+                error!("failed to save snapshot {}: {message}", path.display());
+                failed_events.send(WorldFileFailed { path, message });
+            }
+        }
+    }
+}
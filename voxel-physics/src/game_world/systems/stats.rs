@@ -0,0 +1,60 @@
+use bevy::{prelude::*, render::renderer::RenderDevice};
+
+use crate::game_world::{
+    GameWorldSize, SimulationStats, SimulationStatsUpdated, StatsPartial, StatsReadbacks,
+    StatsReceiver, StatsSender,
+};
+
+/// Maps the partial sums copied by `GameWorldNode` and sends the stats of
+/// every step to the main world once they are available, never blocks.
+pub fn read_stats_sys(
+    mut readbacks: ResMut<StatsReadbacks>,
+    sender: Res<StatsSender>,
+    world_size: Res<GameWorldSize>,
+    render_device: Res<RenderDevice>,
+) {
+    let partials_count = StatsPartial::get_stats_data_size(*world_size) as usize
+        / std::mem::size_of::<StatsPartial>();
+
+    let mut read = Vec::new();
+    for stats in &mut readbacks.0 {
+        let Some(result) = stats.readback.try_read(&render_device) else {
+            continue;
+        };
+
+        match result {
+            Ok(data) => {
+                let partials: Vec<StatsPartial> = bytemuck::pod_collect_to_vec(&data);
+                read.extend(
+                    stats
+                        .steps
+                        .iter()
+                        .zip(partials.chunks(partials_count))
+                        .map(|(&step, partials)| SimulationStats::from_partials(step, partials)),
+                );
+            }
+            Err(err) => {
+                error!("failed to map stats buffer: {err}");
+            }
+        }
+    }
+
+    read.sort_by_key(|stats| stats.step);
+    for stats in read {
+        // the main world is gone while the app shuts down
+        let _ = sender.0.send(stats);
+    }
+}
+
+/// Sends [`SimulationStatsUpdated`] for the stats sent from the render world
+/// and updates [`SimulationStats`] with the latest ones.
+pub fn receive_stats_sys(
+    receiver: Res<StatsReceiver>,
+    mut stats: ResMut<SimulationStats>,
+    mut updated_events: EventWriter<SimulationStatsUpdated>,
+) {
+    for latest in receiver.0.try_iter() {
+        updated_events.send(SimulationStatsUpdated(latest));
+        *stats = latest;
+    }
+}
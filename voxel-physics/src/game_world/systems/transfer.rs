@@ -0,0 +1,110 @@
+use std::mem::size_of;
+
+use bevy::{
+    prelude::*,
+    render::{
+        renderer::{RenderDevice, RenderQueue},
+        MainWorld,
+    },
+};
+
+use crate::{
+    game_world::{
+        get_lost_mass_data_size, snapshot::WorldSnapshot, CellData, FrameRequests, GameWorldData,
+        GameWorldSize, GameWorldUploads, ProgressReceiver, ProgressSender, SimulationProgress,
+        SnapshotRequests, SnapshotSender,
+    },
+    utils::readback::read_buffer_blocking,
+};
+
+/// Moves pending uploads, snapshot and frame requests from the main world into
+/// the render world.
+pub fn extract_transfers_sys(mut commands: Commands, mut main_world: ResMut<MainWorld>) {
+    let uploads = std::mem::take(&mut *main_world.resource_mut::<GameWorldUploads>());
+    let snapshot_requests = std::mem::take(&mut *main_world.resource_mut::<SnapshotRequests>());
+    let frame_requests = std::mem::take(&mut *main_world.resource_mut::<FrameRequests>());
+
+    commands.insert_resource(uploads);
+    commands.insert_resource(snapshot_requests);
+    commands.insert_resource(frame_requests);
+}
+
+/// Reads back `data_prev` for requested snapshots and writes uploads into both
+/// storage buffers. `GameWorldNode` swaps the buffers after dispatching, so
+/// `data_prev` always holds the latest state here.
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_transfers_sys(
+    game_world_data: Res<GameWorldData>,
+    mut uploads: ResMut<GameWorldUploads>,
+    mut snapshot_requests: ResMut<SnapshotRequests>,
+    mut progress: ResMut<SimulationProgress>,
+    snapshot_sender: Res<SnapshotSender>,
+    world_size: Res<GameWorldSize>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if !snapshot_requests.0.is_empty() {
+        let result = read_buffer_blocking(
+            &render_device,
+            &render_queue,
+            &game_world_data.data_prev,
+            0,
+            CellData::get_world_data_size(world_size.0),
+        );
+
+        let snapshot = result
+            .map(|data| WorldSnapshot {
+                size: world_size.0,
+                step: progress.step,
+                cells: bytemuck::pod_collect_to_vec(&data),
+            })
+            .map_err(|err| format!("failed to map world buffer: {err}"));
+
+        for path in snapshot_requests.0.drain(..) {
+            snapshot_sender.0.send((path, snapshot.clone())).unwrap();
+        }
+    }
+
+    if std::mem::take(&mut uploads.reinitialize) {
+        *progress = SimulationProgress::default();
+    }
+
+    // uploads are written after the readback is submitted, so they are not
+    // visible in the snapshots requested during the same frame
+    if let Some(snapshot) = uploads.snapshot.take() {
+        let data = bytemuck::cast_slice(&snapshot.cells);
+        render_queue.write_buffer(&game_world_data.data_prev, 0, data);
+        render_queue.write_buffer(&game_world_data.data_next, 0, data);
+        let lost_mass = vec![0; get_lost_mass_data_size(*world_size) as usize];
+        render_queue.write_buffer(&game_world_data.lost_mass, 0, &lost_mass);
+
+        *progress = SimulationProgress {
+            initialized: true,
+            step: snapshot.step,
+        };
+    }
+
+    let stride = size_of::<CellData>() as u64;
+    for (location, cell) in uploads.cells.drain(..) {
+        let offset = world_size.cell_index(location) as u64 * stride;
+        let data = bytemuck::bytes_of(&cell);
+        render_queue.write_buffer(&game_world_data.data_prev, offset, data);
+        render_queue.write_buffer(&game_world_data.data_next, offset, data);
+    }
+}
+
+pub fn send_progress_sys(progress: Res<SimulationProgress>, sender: Res<ProgressSender>) {
+    // the main world is gone while the app shuts down
+    let _ = sender.0.send(*progress);
+}
+
+/// Updates the main world copy of [`SimulationProgress`] with the latest one
+/// sent from the render world.
+pub fn receive_progress_sys(
+    receiver: Res<ProgressReceiver>,
+    mut progress: ResMut<SimulationProgress>,
+) {
+    if let Some(latest) = receiver.0.try_iter().last() {
+        *progress = latest;
+    }
+}
@@ -0,0 +1,17 @@
+use bevy::prelude::*;
+
+use crate::game_world::VisualizationSettings;
+
+pub fn visualization_control_sys(
+    input: Res<Input<KeyCode>>,
+    mut settings: ResMut<VisualizationSettings>,
+) {
+    if input.just_pressed(KeyCode::V) {
+        settings.mode = if input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            settings.mode.prev()
+        } else {
+            settings.mode.next()
+        };
+        info!("visualization mode: {:?}", settings.mode);
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use voxel_physics::game_world::GameWorldPlugin;
+use voxel_physics::game_world::{GameWorldPlugin, GameWorldViewPlugin};
 
+// the window resolution literals fall back to `f32`
+#[allow(unknown_lints, float_literal_f32_fallback)]
 fn main() {
     App::new()
         // default plugins
@@ -20,6 +22,14 @@ fn main() {
         // third-party plugins
         .add_plugins(WorldInspectorPlugin::new())
         // custom plugins
-        .add_plugins(GameWorldPlugin)
+        .add_plugins((
+            GameWorldPlugin {
+                // optional scenario file or PNG mass map, e.g.
+                // `voxel-physics/assets/scenarios/disk.ron`
+                scenario: std::env::args_os().nth(1).map(Into::into),
+                ..default()
+            },
+            GameWorldViewPlugin,
+        ))
         .run();
 }
@@ -2,3 +2,6 @@ pub mod add_resource;
 pub mod image;
 pub mod pipeline_state;
 pub mod random;
+pub mod readback;
+#[cfg(test)]
+pub mod shaders;
@@ -0,0 +1,207 @@
+use bevy::render::{
+    render_resource::{
+        Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d,
+        ImageCopyBuffer, ImageDataLayout, MapMode, Texture,
+    },
+    renderer::{RenderDevice, RenderQueue},
+};
+use crossbeam_channel::{Receiver, Sender};
+use wgpu::{BufferAsyncError, Maintain, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+/// Copies `size` bytes of `buffer` starting at `offset` to the CPU.
+///
+/// Blocks until the GPU finishes all previously submitted work, so it
+/// shouldn't be used every frame. The buffer must have
+/// [`BufferUsages::COPY_SRC`] usage.
+pub fn read_buffer_blocking(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    buffer: &Buffer,
+    offset: u64,
+    size: u64,
+) -> Result<Vec<u8>, BufferAsyncError> {
+    let staging = render_device.create_buffer(&BufferDescriptor {
+        label: Some("readback staging buffer"),
+        size,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, offset, &staging, 0, size);
+    render_queue.submit([encoder.finish()]);
+
+    map_blocking(render_device, &staging)
+}
+
+/// Size of a row of a 2d texture with 4 bytes per pixel in a buffer it is
+/// copied into, rows of the copy must be aligned.
+pub fn padded_row_size(width: u32) -> u32 {
+    (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Copies the whole 2d texture with 4 bytes per pixel into `staging` with
+/// [`padded_row_size`] rows and submits the copy, so it can be mapped with
+/// [`AsyncReadback::try_read`]. The texture must have
+/// [`TextureUsages::COPY_SRC`](bevy::render::render_resource::TextureUsages::COPY_SRC)
+/// usage.
+pub fn copy_texture_to_staging(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    texture: &Texture,
+    size: (u32, u32),
+    staging: &Buffer,
+) {
+    let (width, height) = size;
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("texture readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: staging,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size(width)),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+}
+
+/// Strips the padding of rows copied by [`copy_texture_to_staging`], so they
+/// are tightly packed.
+pub fn unpad_rows(data: &[u8], width: u32) -> Vec<u8> {
+    data.chunks(padded_row_size(width) as usize)
+        .flat_map(|row| &row[..width as usize * 4])
+        .copied()
+        .collect()
+}
+
+/// Maps the whole staging buffer after the GPU finishes all submitted work.
+fn map_blocking(
+    render_device: &RenderDevice,
+    staging: &Buffer,
+) -> Result<Vec<u8>, BufferAsyncError> {
+    let slice = staging.slice(..);
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    render_device.map_buffer(&slice, MapMode::Read, move |result| {
+        sender.send(result).unwrap();
+    });
+    render_device.poll(Maintain::Wait);
+    // the callback is called by the poll above
+    receiver.recv().unwrap()?;
+
+    let data = slice.get_mapped_range().to_vec();
+    staging.unmap();
+
+    Ok(data)
+}
+
+/// Staging buffer read back without blocking.
+///
+/// Reserved when a copy into `staging` is recorded during the render graph
+/// run, then mapped by [`AsyncReadback::try_read`] polled every frame until
+/// the data is available, which frees the buffer again.
+pub struct AsyncReadback {
+    pub staging: Buffer,
+    /// Whether a copy into `staging` was recorded and not read yet.
+    reserved: bool,
+    /// Whether mapping of `staging` was requested.
+    mapping: bool,
+    mapped_sender: Sender<Result<(), BufferAsyncError>>,
+    mapped_receiver: Receiver<Result<(), BufferAsyncError>>,
+}
+
+impl AsyncReadback {
+    pub fn new(render_device: &RenderDevice, label: &'static str, size: u64) -> Self {
+        let staging = render_device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (mapped_sender, mapped_receiver) = crossbeam_channel::bounded(1);
+
+        Self {
+            staging,
+            reserved: false,
+            mapping: false,
+            mapped_sender,
+            mapped_receiver,
+        }
+    }
+
+    /// Whether a new copy can be recorded into `staging`.
+    pub fn is_free(&self) -> bool {
+        !self.reserved
+    }
+
+    /// Marks `staging` as written by the current frame.
+    pub fn reserve(&mut self) {
+        debug_assert!(self.is_free(), "readback buffer is already reserved");
+        self.reserved = true;
+    }
+
+    /// Requests mapping of the reserved buffer and returns its content once
+    /// it is mapped, never blocks. Must be called after the copy is
+    /// submitted.
+    pub fn try_read(
+        &mut self,
+        render_device: &RenderDevice,
+    ) -> Option<Result<Vec<u8>, BufferAsyncError>> {
+        if !self.reserved {
+            return None;
+        }
+
+        if !self.mapping {
+            let mapped_sender = self.mapped_sender.clone();
+            render_device.map_buffer(&self.staging.slice(..), MapMode::Read, move |result| {
+                mapped_sender.send(result).unwrap();
+            });
+            self.mapping = true;
+        }
+
+        render_device.poll(Maintain::Poll);
+
+        let result = self.mapped_receiver.try_recv().ok()?;
+        let data = result.map(|()| {
+            let data = self.staging.slice(..).get_mapped_range().to_vec();
+            self.staging.unmap();
+            data
+        });
+
+        // the buffer is free again even if mapping failed
+        self.reserved = false;
+        self.mapping = false;
+
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_of_rows_is_stripped() {
+        let width = 3;
+        let padded = padded_row_size(width) as usize;
+        assert_eq!(padded, COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+
+        let mut data = vec![0xff; padded * 2];
+        data[..12].copy_from_slice(&[1; 12]);
+        data[padded..padded + 12].copy_from_slice(&[2; 12]);
+
+        assert_eq!(unpad_rows(&data, width), [[1; 12], [2; 12]].concat());
+    }
+}
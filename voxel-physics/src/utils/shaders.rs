@@ -0,0 +1,212 @@
+//! Shaders composed and validated the same way as by
+//! [`PipelineCache`](bevy::render::render_resource::PipelineCache), so broken
+//! shaders fail tests without running the app or having a GPU.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, render::render_resource::ShaderDefVal};
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    Module, TypeInner,
+};
+use naga_oil::compose::{
+    self, comment_strip_iter::CommentReplaceExt, ComposableModuleDescriptor, Composer,
+    NagaModuleDescriptor, ShaderDefValue,
+};
+
+/// `#import "path"::{items}` directive of a shader.
+#[derive(Debug)]
+pub struct Import {
+    pub path: String,
+    pub items: Vec<String>,
+}
+
+pub fn assets_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("assets")
+}
+
+/// Asset paths of every shader, e.g. `shaders/world_data.wgsl`.
+pub fn shader_paths() -> Vec<String> {
+    let mut paths: Vec<_> = fs::read_dir(assets_dir().join("shaders"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wgsl"))
+        .map(|path| format!("shaders/{}", path.file_name().unwrap().to_str().unwrap()))
+        .collect();
+    paths.sort();
+    paths
+}
+
+pub fn read_shader(path: &str) -> String {
+    fs::read_to_string(assets_dir().join(path))
+        .unwrap_or_else(|err| panic!("failed to read {path}: {err}"))
+}
+
+/// `#import` directives of the shader, with comments removed and items parsed
+/// the same way as by the composer.
+pub fn parse_imports(source: &str) -> Vec<Import> {
+    let mut lines = source.lines();
+    let mut lines = lines.replace_comments();
+    let mut declared_imports = HashMap::new();
+
+    while let Some(line) = lines.next() {
+        if !line.trim_start().starts_with("#import") {
+            continue;
+        }
+
+        // item lists may span several lines
+        let mut directive = line.into_owned();
+        while directive.matches('{').count() > directive.matches('}').count() {
+            let Some(line) = lines.next() else { break };
+            directive.push('\n');
+            directive.push_str(&line);
+        }
+
+        compose::parse_imports::parse_imports(&directive, &mut declared_imports)
+            .unwrap_or_else(|(err, offset)| panic!("{err} at {offset} in {directive}"));
+    }
+
+    let mut imports: Vec<Import> = Vec::new();
+    for full_path in declared_imports.into_values().flatten() {
+        // whole modules are imported without items
+        let (module, item) = match full_path.rsplit_once("::") {
+            Some((module, item)) => (module, Some(item.to_string())),
+            None => (full_path.as_str(), None),
+        };
+        let path = module.trim_matches('"');
+
+        match imports.iter_mut().find(|import| import.path == path) {
+            Some(import) => import.items.extend(item),
+            None => imports.push(Import {
+                path: path.to_string(),
+                items: item.into_iter().collect(),
+            }),
+        }
+    }
+    imports.sort_by(|a, b| a.path.cmp(&b.path));
+    imports
+}
+
+/// Names of the constants, global variables, functions and named types
+/// declared by the module.
+pub fn declared_items(module: &Module) -> HashSet<&str> {
+    let constants = module.constants.iter().map(|(_, constant)| &constant.name);
+    let variables = module
+        .global_variables
+        .iter()
+        .map(|(_, variable)| &variable.name);
+    let functions = module.functions.iter().map(|(_, function)| &function.name);
+    let types = module.types.iter().map(|(_, ty)| &ty.name);
+
+    constants
+        .chain(variables)
+        .chain(functions)
+        .chain(types)
+        .filter_map(Option::as_deref)
+        .collect()
+}
+
+/// Adds every shader imported by the shader at `path` to the composer,
+/// dependencies first, the same way as Bevy registers shader assets.
+fn add_imports(composer: &mut Composer, path: &str, added: &mut HashSet<String>) {
+    for import in parse_imports(&read_shader(path)) {
+        if !added.insert(import.path.clone()) {
+            continue;
+        }
+        add_imports(composer, &import.path, added);
+
+        let source = read_shader(&import.path);
+        let result = composer.add_composable_module(ComposableModuleDescriptor {
+            source: &source,
+            file_path: &import.path,
+            // same as `ShaderImport::AssetPath` module name
+            as_name: Some(format!("\"{}\"", import.path)),
+            ..default()
+        });
+        if let Err(err) = result {
+            let err = err.emit_to_string(composer);
+            panic!("failed to add {}: {err}", import.path);
+        }
+    }
+}
+
+fn naga_shader_defs(shader_defs: Vec<ShaderDefVal>) -> HashMap<String, ShaderDefValue> {
+    shader_defs
+        .into_iter()
+        .map(|def| match def {
+            ShaderDefVal::Bool(name, value) => (name, ShaderDefValue::Bool(value)),
+            ShaderDefVal::Int(name, value) => (name, ShaderDefValue::Int(value)),
+            ShaderDefVal::UInt(name, value) => (name, ShaderDefValue::UInt(value)),
+        })
+        .collect()
+}
+
+/// Composes the shader at `path` with its imports and validates it.
+pub fn validate_shader(path: &str, shader_defs: Vec<ShaderDefVal>) -> Module {
+    let mut composer = Composer::default();
+    add_imports(&mut composer, path, &mut HashSet::new());
+
+    let source = read_shader(path);
+    let module = composer
+        .make_naga_module(NagaModuleDescriptor {
+            source: &source,
+            file_path: path,
+            shader_defs: naga_shader_defs(shader_defs),
+            ..default()
+        })
+        .unwrap_or_else(|err| {
+            panic!(
+                "failed to compose {path}: {}",
+                err.emit_to_string(&composer)
+            )
+        });
+
+    Validator::new(ValidationFlags::all(), Capabilities::default())
+        .validate(&module)
+        .unwrap_or_else(|err| panic!("{path} is invalid: {err:?}"));
+
+    module
+}
+
+/// Byte offsets of the members of a WGSL struct and its size, i.e. the stride
+/// of arrays of it.
+#[derive(Clone, Debug)]
+pub struct StructLayout {
+    pub members: Vec<(String, usize)>,
+    pub size: usize,
+}
+
+impl StructLayout {
+    /// Layout of the struct named `name` declared by the module.
+    pub fn of(module: &Module, name: &str) -> Self {
+        let ty = module
+            .types
+            .iter()
+            .map(|(_, ty)| ty)
+            .find(|ty| ty.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no struct {name} in the module"));
+        let TypeInner::Struct { members, span } = &ty.inner else {
+            panic!("{name} is not a struct");
+        };
+
+        Self {
+            members: members
+                .iter()
+                .map(|member| (member.name.clone().unwrap(), member.offset as usize))
+                .collect(),
+            size: *span as usize,
+        }
+    }
+
+    pub fn offset(&self, member: &str) -> usize {
+        self.members
+            .iter()
+            .find(|(name, _)| name == member)
+            .unwrap_or_else(|| panic!("no member {member}"))
+            .1
+    }
+}